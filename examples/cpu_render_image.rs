@@ -1,10 +1,12 @@
 use std::time::Instant;
 
-use fractals::fractalgen::{self, FractalType, PlaneTransform};
+use fractals::fractalgen::{self, FractalType, Viewport};
+use fractals::fractalgen::colouring::ColourMapType;
+use num::Complex;
 
 fn main() {
 	#[allow(unused)]
-	let (dims_presets, scale_presets, offset_presets) = (
+	let (dims_presets, viewport_presets) = (
 		[
 			(480u32, 360u32),
 			(15360, 8640),
@@ -13,30 +15,21 @@ fn main() {
 			(3840, 2160)
 		],
 		[
-			(0.01, 0.01),
-			(0.0002, 0.0002),
-			(0.000000028662414792174364, 0.000000028662414792174364),
-			(0.0000000012919080187191952, 0.0000000012919080187191952),
-			(0.000000000000007678375638976293, 0.000000000000007678375638976293),
-			(0.05, 0.05),
-		],
-		[
-			(240., 180.),
-			(7680., 4320.),
-			(62327141.65736399, 213.97749196797176),
-			(769919577.7859883, 230132211.5792833),
-			(150615497492901.8, 39666261511409.945),
-			(1920., 1080.)
+			Viewport::new(Complex::new(0., 0.), 1.8),
+			Viewport::new(Complex::new(0., 0.), 0.864),
+			Viewport::new(Complex::new(-1.7864395080144264, -9.738769683837794e-7), 5.159234662591385e-6),
+			Viewport::new(Complex::new(-0.994664966252691, -0.29730941696141516), 2.3254344336945514e-7),
+			Viewport::new(Complex::new(-1.1564823667999493, -0.304572456077091), 1.3821076150157328e-12),
+			Viewport::new(Complex::new(0., 0.), 54.)
 		]
 	);
 
 	let dims = dims_presets[1];//(1920, 1200);//dims_presets[1];
-	let scale = scale_presets[1];//(0.002, 0.002);//scale_presets[1];
-	let offset = offset_presets[1];//(960., 600.);//offset_presets[1];
-	let transform = PlaneTransform::new().scale(scale).base_offset(offset);
+	let viewport = viewport_presets[1];
+	let transform = viewport.to_transform(dims);
 
 	let start = Instant::now();
-	let img = fractalgen::cpu_renderer::multi_threaded::generate_fractal_image(FractalType::MandelbrotSet, dims, &transform, Some(1000));
+	let img = fractalgen::cpu_renderer::multi_threaded::generate_fractal_image(&FractalType::MandelbrotSet, &ColourMapType::HueLoop, dims, &transform, Some(1000));
 	println!("Elapsed: {}", start.elapsed().as_millis());
 	img.save(format!("mandelbrot.png")).unwrap();
 }
\ No newline at end of file
@@ -1,8 +1,12 @@
-use fractals::fractalgen::{self, FractalType, PlaneTransform, ImageBufferFormat, PixelArrayFormat};
+use fractals::fractalgen::{FractalType, Viewport, ImageBufferFormat, PixelArrayFormat};
+use fractals::fractalgen::colouring::ColourMapType;
+use fractals::fractalgen::cpu_renderer::Threadedness;
+use fractals::fractalgen::cpu_renderer::progressive::{self, ProgressiveOptions};
+use num::Complex;
 use minifb::{Window, WindowOptions, Key, MouseButton, MouseMode};
 
 fn main() {
-	let (dims_presets, scale_presets, offset_presets) = (
+	let (dims_presets, viewport_presets) = (
 		[
 			(480u32, 360u32),
 			(15360, 8640),
@@ -10,25 +14,17 @@ fn main() {
 			(480, 360)
 		],
 		[
-			(0.01, 0.01),
-			(0.0005, 0.0005),
-			(0.000000028662414792174364, 0.000000028662414792174364),
-			(0.0000000012919080187191952, 0.0000000012919080187191952),
-			(0.000000000000007678375638976293, 0.000000000000007678375638976293)
-		],
-		[
-			(240., 180.),
-			(7680., 4320.),
-			(62327141.65736399, 213.97749196797176),
-			(769919577.7859883, 230132211.5792833),
-			(150615497492901.8, 39666261511409.945)
+			Viewport::new(Complex::new(0., 0.), 1.8),
+			Viewport::new(Complex::new(0., 0.), 2.16),
+			Viewport::new(Complex::new(-1.7864395080144264, -9.738769683837794e-7), 5.159234662591385e-6),
+			Viewport::new(Complex::new(-0.994664966252691, -0.29730941696141516), 2.3254344336945514e-7),
+			Viewport::new(Complex::new(-1.1564823667999493, -0.304572456077091), 1.3821076150157328e-12)
 		]
 	);
 
 	let dims = dims_presets[0];
-	let scale = scale_presets[0];
-	let offset = offset_presets[0];
-	let mut transform = PlaneTransform::new().scale(scale).base_offset(offset);
+	let viewport = viewport_presets[0];
+	let mut transform = viewport.to_transform(dims);
 	let mut max_iters = 1000;
 
 	let mut window = Window::new(
@@ -47,6 +43,9 @@ fn main() {
 	// Limit to ~20fps
 	window.limit_update_rate(Some(std::time::Duration::from_secs_f32(0.05)));
 
+	// Render at increasing resolution and detail, starting with 8x8 blocks at 100 iterations
+	let progressive_options = ProgressiveOptions::new().block_sizes(vec![8, 4, 2, 1]).initial_max_iterations(100);
+
 	// When this is true, we rerender the fractal
 	let mut rerender = true;
 
@@ -102,14 +101,21 @@ fn main() {
 		});
 
 		if rerender {
-			fractalgen::cpu_renderer::multi_threaded::render_fractal_to(ImageBufferFormat::PixelArray(PixelArrayFormat::Argb32(&mut buffer)), FractalType::MandelbrotSet, dims, &transform, Some(max_iters));
+			// Show each pass as soon as it is done rather than freezing until the full render is finished
+			progressive::render_fractal_to(Threadedness::Multithreaded, &progressive_options, ImageBufferFormat::PixelArray(PixelArrayFormat::Argb32(&mut buffer)), &FractalType::MandelbrotSet, &ColourMapType::HueLoop, dims, &transform, Some(max_iters), |pass, img_buffer_fmt| {
+				if let (false, ImageBufferFormat::PixelArray(PixelArrayFormat::Argb32(px_arr))) = (pass.is_final(), img_buffer_fmt) {
+					window.update_with_buffer(px_arr, dims.0 as usize, dims.1 as usize).unwrap();
+				}
+			});
 			rerender = false;
 		}
 
 		window.update_with_buffer(&buffer, dims.0 as usize, dims.1 as usize).unwrap();
 	}
 
-	println!("Scale: ({}, {}), Offset: ({}, {})", transform.scale_x, transform.scale_y, transform.base_offset_x, transform.base_offset_y);
+	// Print the final location in a form that can be rendered at any resolution
+	let viewport = Viewport::from_transform(&transform, dims);
+	println!("Centre: {}, Radius: {}", viewport.centre, viewport.radius);
 }
 
 fn linear_map(value: f64, start1: f64, stop1: f64, start2: f64, stop2: f64) -> f64{
@@ -0,0 +1,17 @@
+use std::time::Instant;
+
+use fractals::fractalgen::colouring::{ColourMapType, Smooth};
+use fractals::fractalgen::cpu_renderer::Threadedness;
+use fractals::fractalgen::perturbation::{self, DeepViewport, PerturbationOptions};
+
+fn main() {
+	// Next to the Misiurewicz point i, whose spirals repeat at every depth, 1e-100 deep. This is far beyond the precision of f64 and double-double, and is only glitch free because pixels are rebased onto the start of the reference orbit
+	let viewport = DeepViewport::parse("3e-101", &format!("1.{}2", "0".repeat(100)), 1e-100).unwrap();
+	let dims = (960, 720);
+
+	let start = Instant::now();
+	let (img, stats) = perturbation::generate_fractal_image(Threadedness::Multithreaded, &viewport, &Smooth::new(ColourMapType::HueLoop), dims, Some(300), &PerturbationOptions::default());
+	println!("Elapsed: {}", start.elapsed().as_millis());
+	println!("{:?}", stats);
+	img.save("deep_zoom.png").unwrap();
+}
@@ -0,0 +1,277 @@
+//! This module contains the `BigFloat` struct, a binary floating point number with an arbitrary precision mantissa and an unbounded exponent
+//!
+//! It is used where f64 does not have enough precision, such as for the centre and reference orbit of deep zooms, so only supports the operations needed for that: addition, subtraction, multiplication and conversion to and from f64 and decimal strings
+
+use std::cmp::Ordering;
+use std::ops::{Add, Sub, Mul, Neg};
+use num::{BigInt, Signed, Zero, ToPrimitive, Float};
+
+/// The precision in bits of a `BigFloat` created from an f64
+pub const F64_PRECISION: u64 = 53;
+
+/// An arbitrary precision floating point number, with the value mantissa * 2^exponent
+///
+/// The mantissa is truncated to `precision` bits after each operation, where the precision of the result of an operation is the greater of the precisions of the operands
+#[derive(Clone, Debug)]
+pub struct BigFloat {
+	mantissa: BigInt,
+	exponent: i64,
+	precision: u64
+}
+
+impl BigFloat {
+	/// Returns zero with the given precision
+	pub fn zero(precision: u64) -> Self {
+		BigFloat {
+			mantissa: BigInt::zero(),
+			exponent: 0,
+			precision
+		}
+	}
+
+	/// Converts `value` exactly, using `precision` bits (at least 53) for the results of operations on it. Returns None if `value` is not finite
+	pub fn from_f64(value: f64, precision: u64) -> Option<Self> {
+		if !value.is_finite() {
+			return None;
+		}
+
+		let (mantissa, exponent, sign) = value.integer_decode();
+
+		Some(BigFloat {
+			mantissa: BigInt::from(mantissa) * sign,
+			exponent: exponent as i64,
+			precision: precision.max(F64_PRECISION)
+		}.normalised())
+	}
+
+	/// Parses a decimal number such as "-1.25", "0.3e-120" or "7", rounding it to `precision` bits. Returns None if `s` is not a valid decimal number
+	pub fn parse(s: &str, precision: u64) -> Option<Self> {
+		let s = s.trim();
+		let (s, negative) = match s.strip_prefix('-') {
+			Some(rest) => (rest, true),
+			None => (s.strip_prefix('+').unwrap_or(s), false)
+		};
+
+		let (number, exponent) = match s.find(['e', 'E']) {
+			Some(i) => (&s[..i], s[i + 1..].parse::<i64>().ok()?),
+			None => (s, 0)
+		};
+		let (int_part, frac_part) = number.split_once('.').unwrap_or((number, ""));
+
+		if (int_part.is_empty() && frac_part.is_empty()) || !int_part.chars().chain(frac_part.chars()).all(|ch| ch.is_ascii_digit()) {
+			return None;
+		}
+
+		// The value is digits * 10^exponent
+		let digits: BigInt = format!("{}{}", int_part, frac_part).parse().ok()?;
+		let exponent = exponent - frac_part.len() as i64;
+		let digits = if negative { -digits } else { digits };
+
+		let value = if exponent >= 0 {
+			BigFloat {
+				mantissa: digits * BigInt::from(10).pow(exponent as u32),
+				exponent: 0,
+				precision
+			}
+		} else {
+			// Shift the digits left far enough that the quotient still has the full precision
+			let divisor = BigInt::from(10).pow((-exponent) as u32);
+			let shift = (precision + divisor.bits()).saturating_sub(digits.bits()) + 1;
+
+			BigFloat {
+				mantissa: (digits << shift as usize) / divisor,
+				exponent: -(shift as i64),
+				precision
+			}
+		};
+
+		Some(value.normalised())
+	}
+
+	/// Converts to f64, truncating the mantissa. Returns 0 or infinity if the number is out of the range of f64
+	pub fn to_f64(&self) -> f64 {
+		// Keep only as many bits of the mantissa as fit in an f64
+		let excess = self.mantissa.bits().saturating_sub(F64_PRECISION) as i64;
+		let mantissa = (&self.mantissa >> excess as usize).to_f64().unwrap_or(0.);
+		let exponent = self.exponent + excess;
+
+		// Split the exponent as 2^exponent alone may be out of range when the result is not
+		let half = (exponent / 2).clamp(i32::MIN as i64, i32::MAX as i64) as i32;
+		let rest = (exponent - half as i64).clamp(i32::MIN as i64, i32::MAX as i64) as i32;
+		mantissa * 2f64.powi(half) * 2f64.powi(rest)
+	}
+
+	/// Returns the number with its precision changed to `precision` bits, rounding it if the precision is reduced
+	pub fn with_precision(mut self, precision: u64) -> Self {
+		self.precision = precision;
+		self.normalised()
+	}
+
+	pub fn precision(&self) -> u64 {
+		self.precision
+	}
+
+	pub fn is_zero(&self) -> bool {
+		self.mantissa.is_zero()
+	}
+
+	pub fn abs(&self) -> Self {
+		BigFloat {
+			mantissa: self.mantissa.abs(),
+			..self.clone()
+		}
+	}
+
+	/// Truncates the mantissa to the precision, and removes trailing zero bits
+	fn normalised(mut self) -> Self {
+		if self.mantissa.is_zero() {
+			self.exponent = 0;
+			return self;
+		}
+
+		let excess = self.mantissa.bits().saturating_sub(self.precision);
+		let trailing = self.mantissa.trailing_zeros().unwrap_or(0);
+		let shift = excess.max(trailing);
+		if shift > 0 {
+			self.mantissa = if self.mantissa.is_negative() {
+				// Shifting a negative number right rounds towards negative infinity, so shift the magnitude to truncate towards zero
+				-((-self.mantissa) >> shift as usize)
+			} else {
+				self.mantissa >> shift as usize
+			};
+			self.exponent += shift as i64;
+		}
+
+		self
+	}
+
+	/// Returns the mantissas of `self` and `other` shifted to a common exponent, and that exponent
+	fn aligned(&self, other: &BigFloat) -> (BigInt, BigInt, i64) {
+		match self.exponent.cmp(&other.exponent) {
+			Ordering::Greater => (&self.mantissa << (self.exponent - other.exponent) as usize, other.mantissa.clone(), other.exponent),
+			Ordering::Less => (self.mantissa.clone(), &other.mantissa << (other.exponent - self.exponent) as usize, self.exponent),
+			Ordering::Equal => (self.mantissa.clone(), other.mantissa.clone(), self.exponent)
+		}
+	}
+}
+
+impl<'a> Add<&'a BigFloat> for &'a BigFloat {
+	type Output = BigFloat;
+
+	fn add(self, other: &BigFloat) -> BigFloat {
+		let precision = self.precision.max(other.precision);
+
+		// Adding zero would otherwise shift the other mantissa by the exponent of zero
+		if self.is_zero() {
+			return other.clone().with_precision(precision);
+		} else if other.is_zero() {
+			return self.clone().with_precision(precision);
+		}
+
+		let (a, b, exponent) = self.aligned(other);
+
+		BigFloat {
+			mantissa: a + b,
+			exponent,
+			precision
+		}.normalised()
+	}
+}
+
+impl<'a> Sub<&'a BigFloat> for &'a BigFloat {
+	type Output = BigFloat;
+
+	fn sub(self, other: &BigFloat) -> BigFloat {
+		self + &-other
+	}
+}
+
+impl<'a> Mul<&'a BigFloat> for &'a BigFloat {
+	type Output = BigFloat;
+
+	fn mul(self, other: &BigFloat) -> BigFloat {
+		BigFloat {
+			mantissa: &self.mantissa * &other.mantissa,
+			exponent: self.exponent + other.exponent,
+			precision: self.precision.max(other.precision)
+		}.normalised()
+	}
+}
+
+impl Neg for &BigFloat {
+	type Output = BigFloat;
+
+	fn neg(self) -> BigFloat {
+		BigFloat {
+			mantissa: -&self.mantissa,
+			..self.clone()
+		}
+	}
+}
+
+impl Add for BigFloat {
+	type Output = BigFloat;
+
+	fn add(self, other: BigFloat) -> BigFloat {
+		&self + &other
+	}
+}
+
+impl Sub for BigFloat {
+	type Output = BigFloat;
+
+	fn sub(self, other: BigFloat) -> BigFloat {
+		&self - &other
+	}
+}
+
+impl Mul for BigFloat {
+	type Output = BigFloat;
+
+	fn mul(self, other: BigFloat) -> BigFloat {
+		&self * &other
+	}
+}
+
+impl Neg for BigFloat {
+	type Output = BigFloat;
+
+	fn neg(self) -> BigFloat {
+		-&self
+	}
+}
+
+impl PartialEq for BigFloat {
+	/// Compares the values, ignoring the precision
+	fn eq(&self, other: &BigFloat) -> bool {
+		self.mantissa == other.mantissa && (self.exponent == other.exponent || self.is_zero())
+	}
+}
+
+impl PartialOrd for BigFloat {
+	fn partial_cmp(&self, other: &BigFloat) -> Option<Ordering> {
+		let (a, b, _) = self.aligned(other);
+		Some(a.cmp(&b))
+	}
+}
+
+#[test]
+#[cfg(test)]
+fn test_bigfloat_arithmetic() {
+	let a = BigFloat::parse("1.5", 200).unwrap();
+	let b = BigFloat::from_f64(-0.25, 200).unwrap();
+	assert_eq!((&a + &b).to_f64(), 1.25);
+	assert_eq!((&a - &b).to_f64(), 1.75);
+	assert_eq!((&a * &b).to_f64(), -0.375);
+	assert_eq!(BigFloat::parse("-3.75e2", 64).unwrap().to_f64(), -375.);
+
+	// A tiny difference that f64 would lose is kept, and converts to f64 correctly even when it is far below the precision of the larger operand
+	let big = BigFloat::parse("1.00000000000000000000000000000000000001", 256).unwrap();
+	let one = BigFloat::from_f64(1., 256).unwrap();
+	assert!(((&big - &one).to_f64() - 1e-38).abs() < 1e-50);
+	assert!(big > one);
+
+	let tiny = BigFloat::parse("1e-200", 128).unwrap();
+	assert!((tiny.to_f64() - 1e-200).abs() < 1e-212);
+	assert!(BigFloat::parse("1.2.3", 64).is_none());
+}
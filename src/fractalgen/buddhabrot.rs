@@ -0,0 +1,450 @@
+//! This module contains a renderer for the Buddhabrot, the density of the orbits of points that escape the mandelbrot set, and the Nebulabrot, which renders the densities of three bands of escape iterations as red, green and blue
+//!
+//! Rather than colouring each pixel by its own point, points c are sampled at random, and each point of their orbits z = z^2 + c is counted in the pixel it lands in through the inverse of the `PlaneTransform`. The anti-Buddhabrot instead counts the orbits of points that don't escape
+//!
+//! Points can be sampled uniformly from the disk |c| <= 2, or with Metropolis-Hastings, which prefers points whose orbits land in the image so that zoomed views need far fewer samples. The random numbers come from a seeded xorshift generator, so renders are reproducible
+
+use super::{PlaneTransform, ImageBufferFormat, ChannelArrayFormat};
+use super::common;
+use super::cpu_renderer::Threadedness;
+use super::mandelbrot;
+use image::RgbImage;
+use num::Complex;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+/// The number of samples taken when none is given
+pub const DEFAULT_SAMPLES: u64 = 1_000_000;
+
+/// Samples are taken in chunks of this many, each with its own random number generator (and Metropolis-Hastings chain), which are the units of work split between threads
+const CHUNK_SAMPLES: u64 = 1 << 14;
+
+/// How many uniform samples a Metropolis-Hastings chain tries to find a starting point whose orbit lands in the image, before giving up on its chunk
+const MAX_START_ATTEMPTS: u32 = 100_000;
+
+/// The probability that a Metropolis-Hastings proposal is a new uniform sample rather than a mutation of the current point, so that chains don't get stuck in one region
+const RANDOM_PROPOSAL_PROBABILITY: f64 = 0.2;
+
+/// A range of escape iterations. In a Buddhabrot, orbits of points that escape after between `min_iterations` and `max_iterations` iterations (inclusive) are counted in the band
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Band {
+	pub min_iterations: u32,
+	/// Points that haven't escaped after this many iterations are inside the set for the band, so are counted in an anti-Buddhabrot
+	pub max_iterations: u32
+}
+
+impl Band {
+	pub fn new(min_iterations: u32, max_iterations: u32) -> Self {
+		Band { min_iterations, max_iterations }
+	}
+}
+
+/// How the points c are chosen
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Sampling {
+	/// Uniformly from the disk |c| <= 2, which is best for views of the whole set
+	#[default]
+	Uniform,
+	/// With the Metropolis-Hastings algorithm, in proportion to how many points of their orbits land in the image, which is best for zoomed views. Each orbit is counted with a weight of the inverse of that, so the density is the same as with uniform sampling
+	MetropolisHastings
+}
+
+/// How to render a Buddhabrot
+#[derive(Clone, Debug, PartialEq)]
+pub struct BuddhabrotOptions {
+	/// The bands of escape iterations to render. One band is rendered in greyscale, and otherwise the first three are rendered as red, green and blue
+	pub bands: Vec<Band>,
+	pub samples: u64,
+	pub sampling: Sampling,
+	/// Whether to count the orbits of points that don't escape (the anti-Buddhabrot) instead of those that do, ignoring the min iterations of the bands
+	pub anti: bool,
+	/// The seed of the random number generators, so that renders with the same options are the same
+	pub seed: u64,
+	/// The density of each pixel as a fraction of the highest is raised to this power to get its brightness, so values below 1 brighten the fainter orbits
+	pub gamma: f64
+}
+
+impl BuddhabrotOptions {
+	/// A greyscale Buddhabrot of orbits escaping within 1000 iterations, with `DEFAULT_SAMPLES` uniform samples
+	pub fn new() -> Self {
+		BuddhabrotOptions {
+			bands: vec![Band::new(0, 1000)],
+			samples: DEFAULT_SAMPLES,
+			sampling: Sampling::Uniform,
+			anti: false,
+			seed: 0,
+			gamma: 0.5
+		}
+	}
+
+	/// A Nebulabrot of orbits escaping within 5000, 500 and 50 iterations as red, green and blue
+	pub fn nebulabrot() -> Self {
+		BuddhabrotOptions::new().bands(vec![Band::new(0, 5000), Band::new(0, 500), Band::new(0, 50)])
+	}
+
+	/// Builder-style method
+	pub fn bands(mut self, bands: Vec<Band>) -> Self {
+		self.bands = bands;
+		self
+	}
+
+	/// Builder-style method
+	pub fn samples(mut self, samples: u64) -> Self {
+		self.samples = samples;
+		self
+	}
+
+	/// Builder-style method
+	pub fn sampling(mut self, sampling: Sampling) -> Self {
+		self.sampling = sampling;
+		self
+	}
+
+	/// Builder-style method
+	pub fn anti(mut self, anti: bool) -> Self {
+		self.anti = anti;
+		self
+	}
+
+	/// Builder-style method
+	pub fn seed(mut self, seed: u64) -> Self {
+		self.seed = seed;
+		self
+	}
+
+	/// Builder-style method
+	pub fn gamma(mut self, gamma: f64) -> Self {
+		self.gamma = gamma;
+		self
+	}
+}
+
+impl Default for BuddhabrotOptions {
+	fn default() -> Self {
+		BuddhabrotOptions::new()
+	}
+}
+
+/// The density of orbits in each pixel of an image, for each band
+#[derive(Clone, Debug, PartialEq)]
+pub struct DensityBuffer {
+	pub width: u32,
+	pub height: u32,
+	/// The density of each pixel of each band, in row order
+	pub bands: Vec<Vec<f64>>
+}
+
+impl DensityBuffer {
+	fn new(dimensions: (u32, u32), band_count: usize) -> Self {
+		let (width, height) = dimensions;
+
+		DensityBuffer {
+			width,
+			height,
+			bands: vec![vec![0.; width as usize * height as usize]; band_count]
+		}
+	}
+
+	pub fn get(&self, band: usize, x: u32, y: u32) -> f64 {
+		self.bands[band][x as usize + y as usize * self.width as usize]
+	}
+
+	/// Returns the highest density of any pixel in `band`
+	pub fn max(&self, band: usize) -> f64 {
+		self.bands[band].iter().copied().fold(0., f64::max)
+	}
+
+	/// Adds the densities of `other` to these
+	#[cfg(feature = "parallel")]
+	fn merge(mut self, other: DensityBuffer) -> Self {
+		for (band, other_band) in self.bands.iter_mut().zip(other.bands) {
+			for (density, other_density) in band.iter_mut().zip(other_band) {
+				*density += other_density;
+			}
+		}
+		self
+	}
+}
+
+/// Samples points and counts their orbits in each pixel of an image with given dimensions, and a defined transformation from the image coordinate plane to the complex plane
+///
+/// Specify using `threadedness` whether to sample using one thread or multiple. Each thread counts into its own buffer, and the buffers are added together at the end. The result doesn't depend on the number of threads (except for rounding with Metropolis-Hastings, whose weights are fractional). If the transform is not invertible, the buffer is empty
+pub fn compute_density(threadedness: Threadedness, options: &BuddhabrotOptions, dimensions: (u32, u32), transform: &PlaneTransform<f64>) -> DensityBuffer {
+	let inverse = match transform.inverse() {
+		Some(inverse) => inverse,
+		None => return DensityBuffer::new(dimensions, options.bands.len())
+	};
+
+	let sampler = Sampler::new(options, dimensions, transform, inverse);
+	let chunks = options.samples.div_ceil(CHUNK_SAMPLES);
+	let sample_chunk = |mut density: DensityBuffer, chunk: u64| {
+		sampler.sample_chunk(chunk, CHUNK_SAMPLES.min(options.samples - chunk * CHUNK_SAMPLES), &mut density);
+		density
+	};
+
+	match threadedness {
+		Threadedness::Singlethreaded => (0..chunks).fold(DensityBuffer::new(dimensions, options.bands.len()), sample_chunk),
+		#[cfg(feature = "parallel")]
+		Threadedness::Multithreaded => (0..chunks).into_par_iter()
+			.fold(|| DensityBuffer::new(dimensions, options.bands.len()), sample_chunk)
+			.reduce(|| DensityBuffer::new(dimensions, options.bands.len()), DensityBuffer::merge)
+	}
+}
+
+/// Generates an RGB image of the densities in `density`, with the brightness of each pixel being its density as a fraction of the highest in its band raised to the power of `gamma`
+pub fn generate_image_from_density(density: &DensityBuffer, gamma: f64) -> RgbImage {
+	let mut img_buffer: Vec<u8> = vec![0; density.width as usize * density.height as usize * 3];
+
+	colour_density_to(ImageBufferFormat::ChannelArray(ChannelArrayFormat::Rgb8(&mut img_buffer)), density, gamma);
+
+	RgbImage::from_raw(density.width, density.height, img_buffer).unwrap()
+}
+
+/// Writes an RGB image of the densities in `density`, as `generate_image_from_density` does, to the buffer contained within img_buffer_fmt, in the format specified
+pub fn colour_density_to(img_buffer_fmt: ImageBufferFormat, density: &DensityBuffer, gamma: f64) {
+	let maxima: Vec<f64> = (0..density.bands.len()).map(|band| density.max(band)).collect();
+	let brightness = |band: usize, i: usize| -> u8 {
+		match density.bands.get(band) {
+			Some(densities) if maxima[band] > 0. => ((densities[i] / maxima[band]).powf(gamma) * 255.).round() as u8,
+			_ => 0
+		}
+	};
+
+	let rgba: Vec<u8> = (0..(density.width as usize * density.height as usize)).flat_map(|i| {
+		if density.bands.len() == 1 {
+			let grey = brightness(0, i);
+			[grey, grey, grey, 255]
+		} else {
+			[brightness(0, i), brightness(1, i), brightness(2, i), 255]
+		}
+	}).collect();
+
+	common::write_rgba8_to(img_buffer_fmt, &rgba);
+}
+
+/// Generates an RGB image of the Buddhabrot described by `options`, with given dimensions, and a defined transformation from the image coordinate plane to the complex plane
+///
+/// Specify using `threadedness` whether to sample using one thread or multiple
+pub fn generate_fractal_image(threadedness: Threadedness, options: &BuddhabrotOptions, dimensions: (u32, u32), transform: &PlaneTransform<f64>) -> RgbImage {
+	generate_image_from_density(&compute_density(threadedness, options, dimensions, transform), options.gamma)
+}
+
+/// Generates an RGB image of the Buddhabrot described by `options`, with given dimensions, and a defined transformation from the image coordinate plane to the complex plane
+///
+/// The RGB image is written to the buffer contained within img_buffer_fmt, in the format specified. Specify using `threadedness` whether to sample using one thread or multiple
+pub fn render_fractal_to(threadedness: Threadedness, img_buffer_fmt: ImageBufferFormat, options: &BuddhabrotOptions, dimensions: (u32, u32), transform: &PlaneTransform<f64>) {
+	colour_density_to(img_buffer_fmt, &compute_density(threadedness, options, dimensions, transform), options.gamma);
+}
+
+/// The xorshift64* random number generator, which is fast and plenty random enough for sampling
+#[derive(Clone, Debug)]
+struct Xorshift {
+	state: u64
+}
+
+impl Xorshift {
+	/// A generator seeded with `seed`, which is scrambled with splitmix64 so that similar seeds give unrelated sequences, and the state is never 0
+	fn new(seed: u64) -> Self {
+		let mut z = seed.wrapping_add(0x9e3779b97f4a7c15);
+		z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+		z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+		z ^= z >> 31;
+
+		Xorshift { state: if z == 0 { 0x9e3779b97f4a7c15 } else { z } }
+	}
+
+	fn next_u64(&mut self) -> u64 {
+		self.state ^= self.state >> 12;
+		self.state ^= self.state << 25;
+		self.state ^= self.state >> 27;
+		self.state.wrapping_mul(0x2545f4914f6cdd1d)
+	}
+
+	/// Returns a number uniformly distributed in [0, 1)
+	fn next_f64(&mut self) -> f64 {
+		(self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+	}
+
+	/// Returns a point uniformly distributed in the disk |c| <= 2
+	fn next_in_disk(&mut self) -> Complex<f64> {
+		loop {
+			let c = Complex::new(self.next_f64() * 4. - 2., self.next_f64() * 4. - 2.);
+			if c.norm_sqr() <= 4. {
+				return c;
+			}
+		}
+	}
+}
+
+/// Samples the points of one render, counting their orbits through the inverse of its transform
+struct Sampler<'a> {
+	options: &'a BuddhabrotOptions,
+	dimensions: (u32, u32),
+	inverse: PlaneTransform<f64>,
+	/// The largest max iterations of any band, which every orbit is iterated to
+	max_iterations: u32,
+	/// The size of the image in the complex plane, which Metropolis-Hastings mutations are scaled to
+	extent: f64
+}
+
+impl<'a> Sampler<'a> {
+	fn new(options: &'a BuddhabrotOptions, dimensions: (u32, u32), transform: &PlaneTransform<f64>, inverse: PlaneTransform<f64>) -> Self {
+		let (width, height) = dimensions;
+		let origin = transform.transform((0., 0.));
+		let distance = |(x, y): (f64, f64)| ((x - origin.0).powi(2) + (y - origin.1).powi(2)).sqrt();
+
+		Sampler {
+			options,
+			dimensions,
+			inverse,
+			max_iterations: options.bands.iter().map(|band| band.max_iterations).max().unwrap_or(0),
+			extent: distance(transform.transform((width as f64, 0.))).max(distance(transform.transform((0., height as f64))))
+		}
+	}
+
+	/// Takes `samples` samples with the random number generator of `chunk`, counting their orbits in `density`
+	fn sample_chunk(&self, chunk: u64, samples: u64, density: &mut DensityBuffer) {
+		let mut rng = Xorshift::new(self.options.seed.wrapping_add(chunk.wrapping_mul(0x9e3779b97f4a7c15)));
+		let mut orbit = Vec::with_capacity(self.max_iterations as usize);
+		let mut hits = Vec::new();
+
+		match self.options.sampling {
+			Sampling::Uniform => {
+				for _ in 0..samples {
+					self.hits(rng.next_in_disk(), &mut orbit, &mut hits);
+					for &(band, pixel) in &hits {
+						density.bands[band][pixel] += 1.;
+					}
+				}
+			},
+			Sampling::MetropolisHastings => {
+				let mut current = Complex::new(0., 0.);
+				let mut proposal_hits = Vec::new();
+
+				// The chain starts from a point whose orbit lands in the image, as the acceptance ratio is undefined otherwise
+				for _ in 0..MAX_START_ATTEMPTS {
+					current = rng.next_in_disk();
+					self.hits(current, &mut orbit, &mut hits);
+					if !hits.is_empty() {
+						break;
+					}
+				}
+				if hits.is_empty() {
+					return;
+				}
+
+				for _ in 0..samples {
+					let proposal = if rng.next_f64() < RANDOM_PROPOSAL_PROBABILITY { rng.next_in_disk() } else { self.mutate(current, &mut rng) };
+
+					// Both kinds of proposal are symmetric, so the acceptance probability is the ratio of the contributions. Mutations outside the disk are rejected so that the uniform proposals stay symmetric
+					if proposal.norm_sqr() <= 4. {
+						self.hits(proposal, &mut orbit, &mut proposal_hits);
+						if rng.next_f64() * (hits.len() as f64) < proposal_hits.len() as f64 {
+							current = proposal;
+							std::mem::swap(&mut hits, &mut proposal_hits);
+						}
+					}
+
+					let weight = 1. / hits.len() as f64;
+					for &(band, pixel) in &hits {
+						density.bands[band][pixel] += weight;
+					}
+				}
+			}
+		}
+	}
+
+	/// Returns `c` offset in a random direction by a distance between 1e-4 and 0.1 times the size of the image, distributed exponentially so that both small and large steps are taken
+	fn mutate(&self, c: Complex<f64>, rng: &mut Xorshift) -> Complex<f64> {
+		let (r1, r2) = (self.extent * 1e-4, self.extent * 0.1);
+		let radius = r2 * ((r1 / r2).ln() * rng.next_f64()).exp();
+		let angle = rng.next_f64() * std::f64::consts::TAU;
+
+		c + Complex::from_polar(radius, angle)
+	}
+
+	/// Iterates `c` into `orbit`, and finds the pixel of each band that each point of its orbit counted in that band lands in, into `hits` as (band, pixel index)
+	fn hits(&self, c: Complex<f64>, orbit: &mut Vec<Complex<f64>>, hits: &mut Vec<(usize, usize)>) {
+		orbit.clear();
+		hits.clear();
+
+		// Points in the main cardioid and period-2 bulb never escape, so only count in an anti-Buddhabrot
+		let escaped_after = if mandelbrot::check_cardioid_bulb(c).is_some() {
+			if !self.options.anti {
+				return;
+			}
+			iterate_orbit(c, self.max_iterations, orbit);
+			None
+		} else {
+			iterate_orbit(c, self.max_iterations, orbit)
+		};
+
+		let (width, height) = self.dimensions;
+
+		for (band, range) in self.options.bands.iter().enumerate() {
+			let counted = match (self.options.anti, escaped_after) {
+				(false, Some(n)) if n >= range.min_iterations && n <= range.max_iterations => n as usize,
+				(true, None) => range.max_iterations as usize,
+				(true, Some(n)) if n > range.max_iterations => range.max_iterations as usize,
+				_ => 0
+			};
+
+			for z in &orbit[..counted.min(orbit.len())] {
+				let (x, y) = self.inverse.transform((z.re, z.im));
+				if x >= 0. && y >= 0. && x < width as f64 && y < height as f64 {
+					hits.push((band, x as usize + y as usize * width as usize));
+				}
+			}
+		}
+	}
+}
+
+/// Iterates z = z^2 + c from z = 0 up to `max_iterations` times, storing each z in `orbit`, and returns the number of iterations after which |z| passed 2, or None if it didn't escape
+fn iterate_orbit(c: Complex<f64>, max_iterations: u32, orbit: &mut Vec<Complex<f64>>) -> Option<u32> {
+	let mut z = Complex::new(0., 0.);
+
+	for i in 1..=max_iterations {
+		z = Complex::new(z.re * z.re - z.im * z.im + c.re, (z.re + z.re) * z.im + c.im);
+		orbit.push(z);
+
+		if z.norm_sqr() > 4. {
+			return Some(i);
+		}
+	}
+
+	None
+}
+
+#[test]
+#[cfg(test)]
+fn test_buddhabrot() {
+	let dims = (80, 80);
+	let transform = PlaneTransform::new().scale((0.05, 0.05)).base_offset((40., 40.));
+	let options = BuddhabrotOptions::new().bands(vec![Band::new(0, 200), Band::new(0, 50), Band::new(10, 20)]).samples(60_000).seed(7);
+
+	// The Buddhabrot is symmetric about the real axis, so the two halves have about the same density
+	let density = compute_density(Threadedness::Singlethreaded, &options, dims, &transform);
+	let half = |rows: std::ops::Range<u32>| rows.flat_map(|y| (0..dims.0).map(move |x| (x, y))).map(|(x, y)| density.get(0, x, y)).sum::<f64>();
+	let (top, bottom) = (half(0..40), half(40..80));
+	assert!(top > 0. && (top - bottom).abs() / (top + bottom) < 0.05);
+	assert!(density.max(2) > 0. && density.max(2) < density.max(0));
+
+	// The result doesn't depend on the number of threads
+	#[cfg(feature = "parallel")]
+	assert_eq!(compute_density(Threadedness::Multithreaded, &options, dims, &transform), density);
+
+	// Orbits of points that don't escape stay within |z| <= 2, so the corners of an anti-Buddhabrot are empty
+	let anti = compute_density(Threadedness::Singlethreaded, &options.clone().anti(true), dims, &transform);
+	assert!(anti.max(0) > 0. && anti.get(0, 0, 0) == 0. && anti.get(0, 79, 79) == 0.);
+
+	// In a zoomed view, Metropolis-Hastings finds far more orbits that land in the image
+	let zoomed = PlaneTransform::new().scale((0.002, 0.002)).base_offset((40., 40.)).transformed_offset((0.1, 0.6));
+	let coverage = |sampling: Sampling| {
+		let density = compute_density(Threadedness::Singlethreaded, &options.clone().bands(vec![Band::new(0, 200)]).sampling(sampling), dims, &zoomed);
+		density.bands[0].iter().filter(|&&d| d > 0.).count()
+	};
+	assert!(coverage(Sampling::MetropolisHastings) > 2 * coverage(Sampling::Uniform));
+
+	let img = generate_fractal_image(Threadedness::Singlethreaded, &options, dims, &transform);
+	assert!(img.pixels().any(|px| px.0[0] != px.0[2]));
+}
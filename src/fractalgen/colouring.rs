@@ -1,5 +1,93 @@
+//! This module contains the `ColourMap` trait, which maps the escape data of a point to a colour, and the built-in colouring functions
+//!
+//! Any function or closure with the signature `Fn(f64, f64) -> Rgb` (iterations, max iterations) is a `ColourMap`, so the functions in this module can be passed to the renderers directly
+
 use color_space::{Rgb, Hsv};
 use super::common;
+use super::fractal::{Fractal, EscapeData};
+
+/// Maps the escape data of a point to a colour
+pub trait ColourMap {
+	/// Maps a (possibly fractional) number of iterations to a colour
+	fn map(&self, iterations: f64, max_iterations: f64) -> Rgb;
+
+	/// Maps the escape data of a point to a colour. By default, maps the number of iterations using `map`
+	fn colour(&self, data: &EscapeData, max_iterations: u32) -> Rgb {
+		self.map(data.iterations as f64, max_iterations as f64)
+	}
+}
+
+impl<F> ColourMap for F where F: Fn(f64, f64) -> Rgb {
+	fn map(&self, iterations: f64, max_iterations: f64) -> Rgb {
+		self(iterations, max_iterations)
+	}
+}
+
+/// The built-in colour maps, for convenience. Implements `ColourMap` by dispatching to the colouring function of each variant
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ColourMapType {
+	/// `map_to_colour`
+	Hue,
+	/// `map_to_colour_loop`
+	HueLoop,
+	/// `map_to_purple`
+	Purple
+}
+
+/// Wraps a colour map to colour escaped points by their normalised (fractional) iteration count instead of the number of iterations, giving smooth colouring without bands
+///
+/// The escape radius and degree must match those of the fractal being coloured, which `Smooth::for_fractal` does
+#[derive(Clone, Copy, Debug)]
+pub struct Smooth<C> {
+	pub colour_map: C,
+	pub escape_radius: f64,
+	pub degree: f64
+}
+
+impl<C> Smooth<C> {
+	/// Smooth colouring for a fractal with an escape radius of 2 and degree 2, such as the mandelbrot set
+	pub fn new(colour_map: C) -> Self {
+		Smooth {
+			colour_map,
+			escape_radius: 2.,
+			degree: 2.
+		}
+	}
+
+	/// Smooth colouring using the escape radius and degree of `fractal`
+	pub fn for_fractal<F>(colour_map: C, fractal: &F) -> Self where F: Fractal + ?Sized {
+		Smooth {
+			colour_map,
+			escape_radius: fractal.escape_radius(),
+			degree: fractal.degree()
+		}
+	}
+}
+
+impl<C> ColourMap for Smooth<C> where C: ColourMap {
+	fn map(&self, iterations: f64, max_iterations: f64) -> Rgb {
+		self.colour_map.map(iterations, max_iterations)
+	}
+
+	fn colour(&self, data: &EscapeData, max_iterations: u32) -> Rgb {
+		if data.escaped {
+			let nu = normalised_iter_count(data.iterations as f64, data.final_z.norm(), self.escape_radius, self.degree);
+			self.colour_map.map(nu, max_iterations as f64)
+		} else {
+			self.colour_map.map(max_iterations as f64, max_iterations as f64)
+		}
+	}
+}
+
+impl ColourMap for ColourMapType {
+	fn map(&self, iterations: f64, max_iterations: f64) -> Rgb {
+		match self {
+			ColourMapType::Hue => map_to_colour(iterations, max_iterations),
+			ColourMapType::HueLoop => map_to_colour_loop(iterations, max_iterations),
+			ColourMapType::Purple => map_to_purple(iterations, max_iterations)
+		}
+	}
+}
 
 pub fn map_to_colour(iterations: f64, max_iterations: f64) -> Rgb {
 	if iterations == max_iterations {
@@ -17,9 +105,12 @@ pub fn map_to_colour_loop(iterations: f64, max_iterations: f64) -> Rgb {
 	map_to_colour(n, max_iterations)
 }
 
-pub fn normalised_iter_count(iterations: f64, max_iterations: f64) -> Rgb {
-	todo!("get back broke ");
-	todo!("https://en.wikipedia.org/wiki/Plotting_algorithms_for_the_Mandelbrot_set#Continuous_(smooth)_coloring")
+/// Returns the normalised (fractional) iteration count of a point that escaped after `iterations` iterations, where `final_z_norm` is |z| after the last iteration, removing the bands from the colouring
+///
+/// The normalised count is the same as the iteration count if |z| is exactly the escape radius, and decreases continuously as |z| goes further past it. See https://en.wikipedia.org/wiki/Plotting_algorithms_for_the_Mandelbrot_set#Continuous_(smooth)_coloring
+pub fn normalised_iter_count(iterations: f64, final_z_norm: f64, escape_radius: f64, degree: f64) -> f64 {
+	let nu = iterations - (final_z_norm.ln() / escape_radius.ln()).ln() / degree.ln();
+	nu.max(0.)
 }
 
 pub fn map_to_purple(iterations: f64, max_iterations: f64) -> Rgb {
@@ -29,4 +120,13 @@ pub fn map_to_purple(iterations: f64, max_iterations: f64) -> Rgb {
 		let mapped_i = common::linear_map(iterations, 0., max_iterations, 0., 255.);
 		Rgb::new(mapped_i, 0., mapped_i)
 	}
-}
\ No newline at end of file
+}
+
+#[test]
+#[cfg(test)]
+fn test_normalised_iter_count() {
+	// Escaping exactly on the escape radius gives the iteration count, and escaping at radius^degree gives one less
+	assert!((normalised_iter_count(10., 2., 2., 2.) - 10.).abs() < 1e-12);
+	assert!((normalised_iter_count(10., 4., 2., 2.) - 9.).abs() < 1e-12);
+	assert!((normalised_iter_count(10., 8., 2., 3.) - 9.).abs() < 1e-12);
+}
@@ -1,24 +1,54 @@
 //! Contains a few useful functions and most importantly defines the `PlaneTransform` struct
 
-use std::ops::{AddAssign, SubAssign, Mul, MulAssign, Div};
+use std::ops::{Add, Sub, AddAssign, SubAssign, Mul, MulAssign, Div};
 use num::{Zero, One};
+use color_space::Rgb;
+use super::{ImageBufferFormat, PixelArrayFormat, ChannelArrayFormat};
 
-/// Defines a transform from a base plane to a transformed plane
+/// A real number type that the escape-time path can calculate with, such as f64, or `DoubleDouble` for deeper zooms
+///
+/// This is the bound of `PlaneTransform` with the arithmetic needed to iterate a point, so any `PlaneTransform<T>` where `T: Scalar` can be used to render
+pub trait Scalar: Clone + Copy + One + Zero + PartialOrd + MulAssign + AddAssign + SubAssign + Div<f64, Output = Self> + Mul<Output = Self> + Add<Output = Self> + Sub<Output = Self> + Send + Sync {
+	fn from_f64(value: f64) -> Self;
+
+	/// Converts to the nearest f64, losing any extra precision
+	fn to_f64(self) -> f64;
+}
+
+impl Scalar for f64 {
+	fn from_f64(value: f64) -> Self {
+		value
+	}
+
+	fn to_f64(self) -> f64 {
+		self
+	}
+}
+
+/// Defines an affine transform from a base plane to a transformed plane
+///
+/// A point p in the base plane is transformed by offsetting it by the base offset, multiplying it by the matrix [[scale_x, shear_x], [shear_y, scale_y]], and then offsetting it by the transformed offset
 #[derive(Clone, Copy)]
-pub struct PlaneTransform<T> where T: Clone + Copy + One + Zero + PartialOrd + MulAssign + AddAssign + SubAssign + Div<f64, Output = T> + Mul {
+pub struct PlaneTransform<T> where T: Clone + Copy + One + Zero + PartialOrd + MulAssign + AddAssign + SubAssign + Div<f64, Output = T> + Mul<Output = T> {
 	pub scale_x: T,
 	pub scale_y: T,
+	/// How much x in the transformed plane changes with y in the base plane
+	pub shear_x: T,
+	/// How much y in the transformed plane changes with x in the base plane
+	pub shear_y: T,
 	pub base_offset_x: T,
 	pub base_offset_y: T,
 	pub transformed_offset_x: T,
 	pub transformed_offset_y: T
 }
 
-impl<T> PlaneTransform<T> where T: Clone + Copy + One + Zero + PartialOrd + MulAssign + AddAssign + SubAssign + Div<f64, Output = T> + Mul {
+impl<T> PlaneTransform<T> where T: Clone + Copy + One + Zero + PartialOrd + MulAssign + AddAssign + SubAssign + Div<f64, Output = T> + Mul<Output = T> {
 	pub fn new() -> Self {
 		PlaneTransform {
 			scale_x: One::one(),
 			scale_y: One::one(),
+			shear_x: Zero::zero(),
+			shear_y: Zero::zero(),
 			base_offset_x: Zero::zero(),
 			base_offset_y: Zero::zero(),
 			transformed_offset_x: Zero::zero(),
@@ -27,9 +57,13 @@ impl<T> PlaneTransform<T> where T: Clone + Copy + One + Zero + PartialOrd + MulA
 	}
 
 	/// Builder-style method
+	///
+	/// Scales the transformed plane, i.e. multiplies the transformed x and y by scale
 	pub fn scale(mut self, scale: (T, T)) -> Self {
 		self.scale_x *= scale.0;
+		self.shear_x *= scale.0;
 		self.scale_y *= scale.1;
+		self.shear_y *= scale.1;
 
 		self
 	}
@@ -80,6 +114,8 @@ impl<T> PlaneTransform<T> where T: Clone + Copy + One + Zero + PartialOrd + MulA
 		y -= self.base_offset_y;
 		let mut tx = x * self.scale_x;
 		let mut ty = y * self.scale_y;
+		tx += y * self.shear_x;
+		ty += x * self.shear_y;
 		tx -= self.transformed_offset_x;
 		ty -= self.transformed_offset_y;
 
@@ -87,6 +123,102 @@ impl<T> PlaneTransform<T> where T: Clone + Copy + One + Zero + PartialOrd + MulA
 	}
 }
 
+impl PlaneTransform<f64> {
+	/// Builder-style method
+	///
+	/// Multiplies the transformed plane by `matrix` (given as rows), after the existing scale, shear and rotation
+	pub fn matrix(self, matrix: [[f64; 2]; 2]) -> Self {
+		let [[a, b], [c, d]] = matrix;
+
+		PlaneTransform {
+			scale_x: a * self.scale_x + b * self.shear_y,
+			shear_x: a * self.shear_x + b * self.scale_y,
+			shear_y: c * self.scale_x + d * self.shear_y,
+			scale_y: c * self.shear_x + d * self.scale_y,
+			..self
+		}
+	}
+
+	/// Builder-style method
+	///
+	/// Shears the transformed plane, so that transformed x changes by shear.0 per unit of transformed y and vice versa
+	pub fn shear(self, shear: (f64, f64)) -> Self {
+		self.matrix([[1., shear.0], [shear.1, 1.]])
+	}
+
+	/// Builder-style method
+	///
+	/// Rotates the transformed plane anticlockwise by `angle` radians about the point that the base offset maps to
+	pub fn rotate(self, angle: f64) -> Self {
+		let (sin, cos) = angle.sin_cos();
+		self.matrix([[cos, -sin], [sin, cos]])
+	}
+
+	/// Builder-style method
+	///
+	/// Rotates the transformed plane anticlockwise by `angle` radians about the point that `base_point` (a point in the base plane, e.g. the centre of an image) maps to
+	pub fn rotate_about(self, angle: f64, base_point: (f64, f64)) -> Self {
+		let (cx, cy) = self.transform(base_point);
+		let rotated = self.rotate(angle);
+		let (rx, ry) = rotated.transform(base_point);
+
+		// Offset the rotated plane so that base_point maps to the same point as before
+		rotated.transformed_offset((rx - cx, ry - cy))
+	}
+
+	/// Builder-style method
+	///
+	/// Zooms in on the transformed plane by `factor` (zooming out if `factor` is less than 1), keeping the point that `base_point` (a point in the base plane, e.g. a pixel) maps to fixed
+	pub fn zoom_about(self, factor: f64, base_point: (f64, f64)) -> Self {
+		let (cx, cy) = self.transform(base_point);
+		let zoomed = self.scale((1. / factor, 1. / factor));
+		let (zx, zy) = zoomed.transform(base_point);
+
+		zoomed.transformed_offset((zx - cx, zy - cy))
+	}
+
+	/// Returns the determinant of the matrix of the transform. The transform has an inverse only if this is not 0
+	pub fn determinant(&self) -> f64 {
+		self.scale_x * self.scale_y - self.shear_x * self.shear_y
+	}
+
+	/// Returns the transform that applies `self` and then `other`, transforming from the base plane of `self` to the transformed plane of `other`
+	pub fn then(&self, other: &PlaneTransform<f64>) -> Self {
+		// The matrix of the composed transform is other's matrix multiplied by self's, and self's transformed offset and other's base offset are carried through other's matrix
+		let (ox, oy) = (self.transformed_offset_x + other.base_offset_x, self.transformed_offset_y + other.base_offset_y);
+
+		PlaneTransform {
+			transformed_offset_x: other.scale_x * ox + other.shear_x * oy + other.transformed_offset_x,
+			transformed_offset_y: other.shear_y * ox + other.scale_y * oy + other.transformed_offset_y,
+			..self.matrix([[other.scale_x, other.shear_x], [other.shear_y, other.scale_y]])
+		}
+	}
+
+	/// Returns the transform from the transformed plane back to the base plane, or None if the transform is not invertible (its determinant is 0)
+	pub fn inverse(&self) -> Option<Self> {
+		let det = self.determinant();
+		if det == 0. || !det.is_finite() {
+			return None;
+		}
+
+		Some(PlaneTransform {
+			scale_x: self.scale_y / det,
+			scale_y: self.scale_x / det,
+			shear_x: -self.shear_x / det,
+			shear_y: -self.shear_y / det,
+			base_offset_x: -self.transformed_offset_x,
+			base_offset_y: -self.transformed_offset_y,
+			transformed_offset_x: -self.base_offset_x,
+			transformed_offset_y: -self.base_offset_y
+		})
+	}
+
+	/// Maps `coords` in the transformed plane back to the base plane (e.g. a complex coordinate to a pixel), or returns None if the transform is not invertible
+	pub fn inverse_transform(&self, coords: (f64, f64)) -> Option<(f64, f64)> {
+		self.inverse().map(|inverse| inverse.transform(coords))
+	}
+}
+
 pub fn linear_map(value: f64, start1: f64, stop1: f64, start2: f64, stop2: f64) -> f64 {
 	start2 + (stop2 - start2) * ((value - start1) / (stop1 - start1))
 }
@@ -111,4 +243,89 @@ pub fn into_rows_mut<T>(img_buffer: &mut [T], width: u32, height: u32) -> Vec<&m
 		rows.push(p0);
 	}
 	rows
-}
\ No newline at end of file
+}
+
+/// Writes the RGBA pixels in `rgba` (4 channels per pixel, in row order) to the buffer contained within img_buffer_fmt, converting them to the format specified
+pub fn write_rgba8_to(img_buffer_fmt: ImageBufferFormat, rgba: &[u8]) {
+	let pixels = rgba.chunks_exact(4);
+
+	match img_buffer_fmt {
+		ImageBufferFormat::PixelArray(PixelArrayFormat::Argb32(px_arr)) => {
+			for (px, rgba) in px_arr.iter_mut().zip(pixels) {
+				*px = to_0rgb_u8(rgba[0], rgba[1], rgba[2]);
+			}
+		},
+		ImageBufferFormat::ChannelArray(ChannelArrayFormat::Argb8(ch_arr)) => {
+			for (px, rgba) in ch_arr.chunks_exact_mut(4).zip(pixels) {
+				px.copy_from_slice(&[rgba[3], rgba[0], rgba[1], rgba[2]]);
+			}
+		},
+		ImageBufferFormat::ChannelArray(ChannelArrayFormat::Rgb8(ch_arr)) => {
+			for (px, rgba) in ch_arr.chunks_exact_mut(3).zip(pixels) {
+				px.copy_from_slice(&rgba[..3]);
+			}
+		},
+		ImageBufferFormat::ChannelArray(ChannelArrayFormat::Rgba8(ch_arr)) => {
+			ch_arr[..rgba.len()].copy_from_slice(rgba);
+		}
+	}
+}
+
+/// Writes the colours in `colours` (in row order) to the block of pixels `block_width` wide with its top left corner at `corner`, in the buffer contained within img_buffer_fmt of an image `image_width` pixels wide, in the format specified
+pub fn write_rgb_block_to(img_buffer_fmt: &mut ImageBufferFormat, image_width: u32, corner: (u32, u32), block_width: u32, colours: &[Rgb]) {
+	for (row, block_row) in colours.chunks_exact(block_width as usize).enumerate() {
+		let start = corner.0 as usize + (corner.1 as usize + row) * image_width as usize;
+		let pixels = start..(start + block_width as usize);
+
+		match img_buffer_fmt {
+			ImageBufferFormat::PixelArray(PixelArrayFormat::Argb32(px_arr)) => {
+				for (px, rgb) in px_arr[pixels].iter_mut().zip(block_row) {
+					*px = to_0rgb_u8(rgb.r as u8, rgb.g as u8, rgb.b as u8);
+				}
+			},
+			ImageBufferFormat::ChannelArray(ChannelArrayFormat::Argb8(ch_arr)) => {
+				for (px, rgb) in ch_arr[(pixels.start * 4)..(pixels.end * 4)].chunks_exact_mut(4).zip(block_row) {
+					px.copy_from_slice(&[255, rgb.r as u8, rgb.g as u8, rgb.b as u8]);
+				}
+			},
+			ImageBufferFormat::ChannelArray(ChannelArrayFormat::Rgb8(ch_arr)) => {
+				for (px, rgb) in ch_arr[(pixels.start * 3)..(pixels.end * 3)].chunks_exact_mut(3).zip(block_row) {
+					px.copy_from_slice(&[rgb.r as u8, rgb.g as u8, rgb.b as u8]);
+				}
+			},
+			ImageBufferFormat::ChannelArray(ChannelArrayFormat::Rgba8(ch_arr)) => {
+				for (px, rgb) in ch_arr[(pixels.start * 4)..(pixels.end * 4)].chunks_exact_mut(4).zip(block_row) {
+					px.copy_from_slice(&[rgb.r as u8, rgb.g as u8, rgb.b as u8, 255]);
+				}
+			}
+		}
+	}
+}
+
+#[test]
+#[cfg(test)]
+fn test_plane_transform_affine() {
+	let close = |a: (f64, f64), b: (f64, f64)| (a.0 - b.0).abs() < 1e-9 && (a.1 - b.1).abs() < 1e-9;
+
+	let transform = PlaneTransform::new().scale((0.01, 0.02)).base_offset((240., 180.)).transformed_offset((0.5, -0.25)).rotate(0.7).shear((0.1, -0.3));
+
+	// Mapping a point and mapping it back should give the original point
+	let inverse = transform.inverse().unwrap();
+	for point in [(0., 0.), (240., 180.), (479., 13.)] {
+		assert!(close(inverse.transform(transform.transform(point)), point));
+		assert!(close(transform.inverse_transform(transform.transform(point)).unwrap(), point));
+	}
+
+	// Composing a transform with its inverse should give the identity
+	let identity = transform.then(&inverse);
+	assert!(close(identity.transform((12., 34.)), (12., 34.)));
+
+	// Rotating and zooming about a point should leave it fixed, and rotating by a quarter turn should swap the axes
+	let centre = (240., 180.);
+	assert!(close(transform.rotate_about(1.3, centre).transform(centre), transform.transform(centre)));
+	assert!(close(transform.zoom_about(4., (10., 20.)).transform((10., 20.)), transform.transform((10., 20.))));
+	let quarter = PlaneTransform::new().rotate(std::f64::consts::FRAC_PI_2);
+	assert!(close(quarter.transform((1., 0.)), (0., 1.)));
+
+	assert!(PlaneTransform::new().scale((0., 1.)).inverse().is_none());
+}
@@ -1,40 +1,169 @@
 //! This module contains the functions `generate_fractal_image` and `render_fractal_to`, allowing to specify using an enum whether to render them using multiple threads or one
 //!
+//! Rendering can also be split into two stages: `compute_iterations` calculates the raw escape data of every pixel, which `generate_image_from_iterations` and `colour_iterations_to` then map to colours
+//!
 //! Alternatively, it also contains the modules `single_threaded` and `multi_threaded` with those same functions, using one thread or multiple respectively
 //!
-//! These calculations are all done exclusively on the CPU
+//! The module `tiled` splits the image into tiles that threads take in turn, with options for the size and order of the tiles and which thread pool to use, and is what `multi_threaded` renders with
+//!
+//! The module `progressive` renders a coarse image first and then refines it, so that it can be shown before the render is finished
+//!
+//! The module `subdivision` renders the same images as the others for the mandelbrot and julia sets while calculating far fewer pixels, by filling rectangles whose borders have the same number of iterations where no filament can be hidden inside them
+//!
+//! The module `simd` renders the built-in fractals several pixels at a time with SIMD instructions, specified using the enum `Vectorisation`
+//!
+//! These calculations are all done exclusively on the CPU. Using multiple threads requires the `parallel` feature
+//!
+//! The transform may use any `Scalar`, so e.g. a `PlaneTransform<DoubleDouble>` renders zooms deeper than f64 allows
 
 /// This module contains the singlethreaded variants of the functions `generate_fractal_image` and `render_fractal_to`
 pub mod single_threaded;
 /// This module contains the multithreaded variants of the functions `generate_fractal_image` and `render_fractal_to`
+#[cfg(feature = "parallel")]
 pub mod multi_threaded;
+/// This module contains variants of the functions `generate_fractal_image`, `render_fractal_to` and `compute_iterations` that render the image in tiles on multiple threads, configured with `TileOptions`
+#[cfg(feature = "parallel")]
+pub mod tiled;
+/// This module contains a variant of the function `render_fractal_to` that renders in passes of increasing resolution, calling back after each one
+pub mod progressive;
+/// This module contains variants of the functions `generate_fractal_image`, `render_fractal_to` and `compute_iterations` (and `_with_options` variants) that only calculate the borders of rectangles with the same number of iterations (Mariani-Silver subdivision)
+pub mod subdivision;
+/// This module contains variants of the functions `generate_fractal_image`, `render_fractal_to` and `compute_iterations` for the built-in fractals that iterate a group of pixels at once with SIMD instructions
+pub mod simd;
 
-use super::{FractalType, PlaneTransform, ImageBufferFormat};
+use super::{Fractal, PlaneTransform, Scalar, ImageBufferFormat, IterationBuffer, Error, RenderOptions};
+use super::colouring::ColourMap;
 use image::RgbImage;
 
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Threadedness {
 	Singlethreaded,
+	#[cfg(feature = "parallel")]
 	Multithreaded
 }
 
-/// Generates an RGB image of the specified fractal, with given dimensions, and a defined transformation from the image coordinate plane to the complex plane, and the max_iterations is the amount of detail (50-100 being low, >=1000 being high, default 100)
+/// How many pixels to iterate at once. The SIMD variants use AVX if the CPU supports it (detected at runtime), and otherwise fall back to iterating each pixel of the group in turn
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Vectorisation {
+	/// One pixel at a time
+	Scalar,
+	/// 4 pixels at a time in double precision, giving the same results as `Scalar`
+	F64x4,
+	/// 8 pixels at a time in single precision, which is faster but only precise enough for shallow zooms
+	F32x8
+}
+
+impl Vectorisation {
+	/// Returns the fastest vectorisation supported by the CPU that gives the same results as `Scalar`
+	pub fn detect() -> Self {
+		if Vectorisation::F64x4.is_accelerated() {
+			Vectorisation::F64x4
+		} else {
+			Vectorisation::Scalar
+		}
+	}
+
+	/// Returns the number of pixels iterated at once
+	pub fn lanes(&self) -> usize {
+		match self {
+			Vectorisation::Scalar => 1,
+			Vectorisation::F64x4 => 4,
+			Vectorisation::F32x8 => 8
+		}
+	}
+
+	/// Returns true if the CPU has SIMD instructions for this vectorisation, rather than it falling back to iterating one pixel at a time
+	pub fn is_accelerated(&self) -> bool {
+		match self {
+			Vectorisation::Scalar => false,
+			#[cfg(target_arch = "x86_64")]
+			Vectorisation::F64x4 | Vectorisation::F32x8 => is_x86_feature_detected!("avx"),
+			#[cfg(not(target_arch = "x86_64"))]
+			Vectorisation::F64x4 | Vectorisation::F32x8 => false
+		}
+	}
+}
+
+/// Generates an RGB image of `fractal` coloured with `colour_map`, with given dimensions, and a defined transformation from the image coordinate plane to the complex plane, and the max_iterations is the amount of detail (50-100 being low, >=1000 being high, default 100)
 ///
 /// Specify using `threadedness` whether to calculate using one thread or multiple
-pub fn generate_fractal_image(threadedness: Threadedness, fractal_type: FractalType, dimensions: (u32, u32), transform: &PlaneTransform<f64>, max_iterations: Option<u32>) -> RgbImage {
+pub fn generate_fractal_image<T, F, C>(threadedness: Threadedness, fractal: &F, colour_map: &C, dimensions: (u32, u32), transform: &PlaneTransform<T>, max_iterations: Option<u32>) -> RgbImage where T: Scalar, F: Fractal<T> + Sync + ?Sized, C: ColourMap + Sync + ?Sized {
 	match threadedness {
-		Threadedness::Singlethreaded => single_threaded::generate_fractal_image(fractal_type, dimensions, transform, max_iterations),
-		Threadedness::Multithreaded => multi_threaded::generate_fractal_image(fractal_type, dimensions, transform, max_iterations)
+		Threadedness::Singlethreaded => single_threaded::generate_fractal_image(fractal, colour_map, dimensions, transform, max_iterations),
+		#[cfg(feature = "parallel")]
+		Threadedness::Multithreaded => multi_threaded::generate_fractal_image(fractal, colour_map, dimensions, transform, max_iterations)
 	}
 }
 
-/// Generates an RGB image of the specified fractal, with given dimensions, and a defined transformation from the image coordinate plane to the complex plane, and the max_iterations is the amount of detail (50-100 being low, >=1000 being high, default 100)
+/// Generates an RGB image of `fractal` coloured with `colour_map`, with given dimensions, and a defined transformation from the image coordinate plane to the complex plane, and the max_iterations is the amount of detail (50-100 being low, >=1000 being high, default 100)
 ///
 /// The RGB image is written to the buffer contained within img_buffer_fmt, in the format specified
 ///
 /// Specify using `threadedness` whether to calculate using one thread or multiple
-pub fn render_fractal_to(threadedness: Threadedness, img_buffer_fmt: ImageBufferFormat, fractal_type: FractalType, dimensions: (u32, u32), transform: &PlaneTransform<f64>, max_iterations: Option<u32>) {
+pub fn render_fractal_to<T, F, C>(threadedness: Threadedness, img_buffer_fmt: ImageBufferFormat, fractal: &F, colour_map: &C, dimensions: (u32, u32), transform: &PlaneTransform<T>, max_iterations: Option<u32>) where T: Scalar, F: Fractal<T> + Sync + ?Sized, C: ColourMap + Sync + ?Sized {
 	match threadedness {
-		Threadedness::Singlethreaded => single_threaded::render_fractal_to(img_buffer_fmt, fractal_type, dimensions, transform, max_iterations),
-		Threadedness::Multithreaded => multi_threaded::render_fractal_to(img_buffer_fmt, fractal_type, dimensions, transform, max_iterations)
+		Threadedness::Singlethreaded => single_threaded::render_fractal_to(img_buffer_fmt, fractal, colour_map, dimensions, transform, max_iterations),
+		#[cfg(feature = "parallel")]
+		Threadedness::Multithreaded => multi_threaded::render_fractal_to(img_buffer_fmt, fractal, colour_map, dimensions, transform, max_iterations)
 	}
-}
\ No newline at end of file
+}
+
+/// Same as `render_fractal_to`, but checks the cancellation token of `options` between rows (or tiles if multithreaded), returning `Error::Cancelled` with the image incomplete if it is cancelled, and reports progress to its progress callback
+#[allow(clippy::too_many_arguments)]
+pub fn render_fractal_to_with_options<T, F, C>(threadedness: Threadedness, options: &RenderOptions, img_buffer_fmt: ImageBufferFormat, fractal: &F, colour_map: &C, dimensions: (u32, u32), transform: &PlaneTransform<T>, max_iterations: Option<u32>) -> Result<(), Error> where T: Scalar, F: Fractal<T> + Sync + ?Sized, C: ColourMap + Sync + ?Sized {
+	match threadedness {
+		Threadedness::Singlethreaded => single_threaded::render_fractal_to_with_options(options, img_buffer_fmt, fractal, colour_map, dimensions, transform, max_iterations),
+		#[cfg(feature = "parallel")]
+		Threadedness::Multithreaded => multi_threaded::render_fractal_to_with_options(options, img_buffer_fmt, fractal, colour_map, dimensions, transform, max_iterations)
+	}
+}
+
+/// Calculates the escape data of every pixel of `fractal`, with given dimensions, and a defined transformation from the image coordinate plane to the complex plane, and the max_iterations is the amount of detail (50-100 being low, >=1000 being high, default 100)
+///
+/// If `track_derivative` is true, the derivative is also calculated for fractals that support it. Specify using `threadedness` whether to calculate using one thread or multiple
+pub fn compute_iterations<T, F>(threadedness: Threadedness, fractal: &F, dimensions: (u32, u32), transform: &PlaneTransform<T>, max_iterations: Option<u32>, track_derivative: bool) -> IterationBuffer where T: Scalar, F: Fractal<T> + Sync + ?Sized {
+	match threadedness {
+		Threadedness::Singlethreaded => single_threaded::compute_iterations(fractal, dimensions, transform, max_iterations, track_derivative),
+		#[cfg(feature = "parallel")]
+		Threadedness::Multithreaded => multi_threaded::compute_iterations(fractal, dimensions, transform, max_iterations, track_derivative)
+	}
+}
+
+/// Generates an RGB image from the escape data in `iterations`, mapping each pixel to a colour with `colour_map`
+///
+/// Specify using `threadedness` whether to calculate using one thread or multiple
+pub fn generate_image_from_iterations<C>(threadedness: Threadedness, iterations: &IterationBuffer, colour_map: &C) -> RgbImage where C: ColourMap + Sync + ?Sized {
+	match threadedness {
+		Threadedness::Singlethreaded => single_threaded::generate_image_from_iterations(iterations, colour_map),
+		#[cfg(feature = "parallel")]
+		Threadedness::Multithreaded => multi_threaded::generate_image_from_iterations(iterations, colour_map)
+	}
+}
+
+/// Maps each pixel of the escape data in `iterations` to a colour with `colour_map`, writing it to the buffer contained within img_buffer_fmt, in the format specified
+///
+/// Specify using `threadedness` whether to calculate using one thread or multiple
+pub fn colour_iterations_to<C>(threadedness: Threadedness, img_buffer_fmt: ImageBufferFormat, iterations: &IterationBuffer, colour_map: &C) where C: ColourMap + Sync + ?Sized {
+	match threadedness {
+		Threadedness::Singlethreaded => single_threaded::colour_iterations_to(img_buffer_fmt, iterations, colour_map),
+		#[cfg(feature = "parallel")]
+		Threadedness::Multithreaded => multi_threaded::colour_iterations_to(img_buffer_fmt, iterations, colour_map)
+	}
+}
+
+#[test]
+#[cfg(all(test, feature = "parallel"))]
+fn test_iteration_buffer_colouring() {
+	use super::{FractalType, colouring, julia};
+
+	let dims = (64, 48);
+	let transform = PlaneTransform::new().scale((0.05, 0.05)).base_offset((32., 24.));
+
+	let direct = single_threaded::generate_fractal_image(&FractalType::JuliaSet { c: julia::DEFAULT_C }, &colouring::ColourMapType::Hue, dims, &transform, Some(200));
+
+	let iterations = multi_threaded::compute_iterations(&FractalType::JuliaSet { c: julia::DEFAULT_C }, dims, &transform, Some(200), false);
+	assert_eq!(iterations.data, single_threaded::compute_iterations(&FractalType::JuliaSet { c: julia::DEFAULT_C }, dims, &transform, Some(200), false).data);
+
+	let recoloured = single_threaded::generate_image_from_iterations(&iterations, &colouring::map_to_colour);
+	assert_eq!(direct, recoloured);
+}
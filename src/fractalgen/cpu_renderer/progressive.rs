@@ -0,0 +1,188 @@
+use super::Threadedness;
+use super::super::{Fractal, PlaneTransform, Scalar, ImageBufferFormat, PixelArrayFormat, ChannelArrayFormat};
+use super::super::{common, fractal};
+use super::super::colouring::{ColourMap, Smooth};
+use super::super::renderer::RenderJob;
+use color_space::Rgb;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+/// How to refine a progressive render
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProgressiveOptions {
+	/// The width and height of the blocks of pixels coloured the same in each pass, from coarsest to finest. Each should divide the one before it so that pixels calculated in earlier passes are not calculated again, and the last should be 1 for the render to be complete
+	pub block_sizes: Vec<u32>,
+	/// If set, the max iterations increase geometrically from this in the first pass to the max iterations of the render in the last, so that the early passes are faster still
+	pub initial_max_iterations: Option<u32>
+}
+
+impl ProgressiveOptions {
+	/// Passes with blocks of 16, 8, 4, 2 and then 1 pixel, all with the same max iterations
+	pub fn new() -> Self {
+		ProgressiveOptions {
+			block_sizes: vec![16, 8, 4, 2, 1],
+			initial_max_iterations: None
+		}
+	}
+
+	/// Builder-style method
+	pub fn block_sizes(mut self, block_sizes: Vec<u32>) -> Self {
+		self.block_sizes = block_sizes;
+		self
+	}
+
+	/// Builder-style method
+	pub fn initial_max_iterations(mut self, initial_max_iterations: u32) -> Self {
+		self.initial_max_iterations = Some(initial_max_iterations);
+		self
+	}
+
+	/// Returns the max iterations of each pass, given the max iterations of the render
+	fn pass_max_iterations(&self, max_iterations: u32) -> Vec<u32> {
+		let count = self.block_sizes.len();
+
+		(0..count).map(|pass| match self.initial_max_iterations {
+			Some(initial) if count > 1 && initial < max_iterations => {
+				let t = pass as f64 / (count - 1) as f64;
+				((initial.max(1) as f64) * (max_iterations as f64 / initial.max(1) as f64).powf(t)).round() as u32
+			},
+			_ => max_iterations
+		}).collect()
+	}
+}
+
+impl Default for ProgressiveOptions {
+	fn default() -> Self {
+		ProgressiveOptions::new()
+	}
+}
+
+/// Describes a pass of a progressive render that has just finished
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PassInfo {
+	/// The index of the pass, starting from 0
+	pub pass: usize,
+	/// The total number of passes
+	pub pass_count: usize,
+	pub block_size: u32,
+	pub max_iterations: u32
+}
+
+impl PassInfo {
+	/// Returns true if this is the last pass, after which the image is complete
+	pub fn is_final(&self) -> bool {
+		self.pass + 1 == self.pass_count
+	}
+}
+
+/// Renders `fractal` coloured with `colour_map` in several passes of increasing resolution, with given dimensions, and a defined transformation from the image coordinate plane to the complex plane, and the max_iterations is the amount of detail of the last pass (50-100 being low, >=1000 being high, default 100)
+///
+/// Each pass calculates one pixel of every block and colours the whole block with it, writing to the buffer contained within img_buffer_fmt, in the format specified, and then calls `on_pass` with the buffer so that it can be shown before the render is finished. Specify using `threadedness` whether to calculate each pass using one thread or multiple
+#[allow(clippy::too_many_arguments)]
+pub fn render_fractal_to<T, F, C, P>(threadedness: Threadedness, options: &ProgressiveOptions, img_buffer_fmt: ImageBufferFormat, fractal: &F, colour_map: &C, dimensions: (u32, u32), transform: &PlaneTransform<T>, max_iterations: Option<u32>, mut on_pass: P) where T: Scalar, F: Fractal<T> + Sync + ?Sized, C: ColourMap + Sync + ?Sized, P: FnMut(&PassInfo, &ImageBufferFormat) {
+	let (width, height) = dimensions;
+	let mut img_buffer_fmt = img_buffer_fmt;
+
+	let pass_max_iterations = options.pass_max_iterations(max_iterations.unwrap_or(fractal::DEFAULT_MAX_ITERATIONS));
+	let mut previous: Option<(u32, u32)> = None;
+
+	for (pass, (&block_size, &max_iterations)) in options.block_sizes.iter().zip(&pass_max_iterations).enumerate() {
+		let block_size = block_size.max(1);
+
+		// Pixels calculated by the previous pass with the same max iterations already have the right colour over this pass's block
+		let is_calculated = |x: u32, y: u32| match previous {
+			Some((previous_size, previous_max_iterations)) => previous_max_iterations == max_iterations && previous_size.is_multiple_of(block_size) && x.is_multiple_of(previous_size) && y.is_multiple_of(previous_size),
+			None => false
+		};
+
+		let sample_row = |y: u32| -> Vec<(u32, Rgb)> {
+			(0..width).step_by(block_size as usize)
+				.filter(|&x| !is_calculated(x, y))
+				.map(|x| (x, fractal::calculate_pixel(fractal, colour_map, x, y, transform, Some(max_iterations))))
+				.collect()
+		};
+
+		let ys: Vec<u32> = (0..height).step_by(block_size as usize).collect();
+		let rows: Vec<Vec<(u32, Rgb)>> = match threadedness {
+			Threadedness::Singlethreaded => ys.iter().map(|&y| sample_row(y)).collect(),
+			#[cfg(feature = "parallel")]
+			Threadedness::Multithreaded => ys.par_iter().map(|&y| sample_row(y)).collect()
+		};
+
+		for (&y, row) in ys.iter().zip(rows) {
+			for (x, rgb) in row {
+				fill_block(&mut img_buffer_fmt, dimensions, (x, y), block_size, rgb);
+			}
+		}
+
+		previous = Some((block_size, max_iterations));
+
+		on_pass(&PassInfo { pass, pass_count: options.block_sizes.len(), block_size, max_iterations }, &img_buffer_fmt);
+	}
+}
+
+/// Renders `job` progressively as `render_fractal_to` does, using its fractal, colour map and options
+pub fn render_job_to<P>(threadedness: Threadedness, options: &ProgressiveOptions, job: &RenderJob, img_buffer_fmt: ImageBufferFormat, on_pass: P) where P: FnMut(&PassInfo, &ImageBufferFormat) {
+	let fractal = job.checked_fractal();
+
+	if job.smooth_colouring {
+		render_fractal_to(threadedness, options, img_buffer_fmt, &fractal, &Smooth::for_fractal(job.colour_map, &fractal), job.dimensions, &job.transform, job.max_iterations, on_pass);
+	} else {
+		render_fractal_to(threadedness, options, img_buffer_fmt, &fractal, &job.colour_map, job.dimensions, &job.transform, job.max_iterations, on_pass);
+	}
+}
+
+/// Colours the block of `size` pixels with its top left corner at `corner` with `rgb`, clipped to the image, in the buffer contained within img_buffer_fmt
+fn fill_block(img_buffer_fmt: &mut ImageBufferFormat, dimensions: (u32, u32), corner: (u32, u32), size: u32, rgb: Rgb) {
+	let (width, height) = dimensions;
+	let (r, g, b) = (rgb.r as u8, rgb.g as u8, rgb.b as u8);
+
+	for y in corner.1..(corner.1 + size).min(height) {
+		let start = corner.0 as usize + y as usize * width as usize;
+		let pixels = start..(start + ((corner.0 + size).min(width) - corner.0) as usize);
+
+		match img_buffer_fmt {
+			ImageBufferFormat::PixelArray(PixelArrayFormat::Argb32(px_arr)) => {
+				px_arr[pixels].fill(common::to_0rgb_u8(r, g, b));
+			},
+			ImageBufferFormat::ChannelArray(ChannelArrayFormat::Argb8(ch_arr)) => {
+				for px in ch_arr[(pixels.start * 4)..(pixels.end * 4)].chunks_exact_mut(4) {
+					px.copy_from_slice(&[255, r, g, b]);
+				}
+			},
+			ImageBufferFormat::ChannelArray(ChannelArrayFormat::Rgb8(ch_arr)) => {
+				for px in ch_arr[(pixels.start * 3)..(pixels.end * 3)].chunks_exact_mut(3) {
+					px.copy_from_slice(&[r, g, b]);
+				}
+			},
+			ImageBufferFormat::ChannelArray(ChannelArrayFormat::Rgba8(ch_arr)) => {
+				for px in ch_arr[(pixels.start * 4)..(pixels.end * 4)].chunks_exact_mut(4) {
+					px.copy_from_slice(&[r, g, b, 255]);
+				}
+			}
+		}
+	}
+}
+
+#[test]
+#[cfg(test)]
+fn test_progressive_final_pass_matches_direct() {
+	use super::super::FractalType;
+	use super::super::colouring::ColourMapType;
+
+	let dims = (70, 45);
+	let transform = PlaneTransform::new().scale((0.04, 0.04)).base_offset((45., 22.));
+	let expected = super::single_threaded::generate_fractal_image(&FractalType::MandelbrotSet, &ColourMapType::Hue, dims, &transform, Some(300));
+
+	for options in [ProgressiveOptions::new(), ProgressiveOptions::new().block_sizes(vec![9, 3, 1]).initial_max_iterations(20)] {
+		let mut img_buffer = vec![0u8; dims.0 as usize * dims.1 as usize * 3];
+		let mut passes = Vec::new();
+
+		render_fractal_to(Threadedness::Singlethreaded, &options, ImageBufferFormat::ChannelArray(ChannelArrayFormat::Rgb8(&mut img_buffer)), &FractalType::MandelbrotSet, &ColourMapType::Hue, dims, &transform, Some(300), |info, _| passes.push(*info));
+
+		assert_eq!(passes.len(), options.block_sizes.len());
+		assert!(passes.last().unwrap().is_final() && passes.last().unwrap().max_iterations == 300);
+		assert!(passes.windows(2).all(|pair| pair[0].max_iterations <= pair[1].max_iterations));
+		assert_eq!(&img_buffer, expected.as_raw());
+	}
+}
@@ -0,0 +1,274 @@
+use super::{Threadedness, Vectorisation};
+use super::super::{FractalType, PlaneTransform, ImageBufferFormat, ChannelArrayFormat, IterationBuffer, EscapeData};
+use super::super::{common, fractal};
+use super::super::colouring::ColourMap;
+use image::RgbImage;
+use num::{Complex, Float};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+/// The largest max iterations that the f32x8 path counts exactly, as it counts iterations in f32
+const F32_MAX_ITERATIONS: u32 = 1 << 24;
+
+/// Generates an RGB image of `fractal_type` coloured with `colour_map`, with given dimensions, and a defined transformation from the image coordinate plane to the complex plane, and the max_iterations is the amount of detail (50-100 being low, >=1000 being high, default 100)
+///
+/// Specify using `threadedness` whether to calculate using one thread or multiple, and using `vectorisation` how many pixels to iterate at once
+pub fn generate_fractal_image<C>(threadedness: Threadedness, vectorisation: Vectorisation, fractal_type: FractalType, colour_map: &C, dimensions: (u32, u32), transform: &PlaneTransform<f64>, max_iterations: Option<u32>) -> RgbImage where C: ColourMap + Sync + ?Sized {
+	let (width, height) = dimensions;
+
+	let mut img_buffer: Vec<u8> = vec![0; width as usize * height as usize * 3];
+
+	render_fractal_to(threadedness, vectorisation, ImageBufferFormat::ChannelArray(ChannelArrayFormat::Rgb8(&mut img_buffer)), fractal_type, colour_map, dimensions, transform, max_iterations);
+
+	RgbImage::from_raw(width, height, img_buffer).unwrap()
+}
+
+/// Generates an RGB image of `fractal_type` coloured with `colour_map`, with given dimensions, and a defined transformation from the image coordinate plane to the complex plane, and the max_iterations is the amount of detail (50-100 being low, >=1000 being high, default 100)
+///
+/// The RGB image is written to the buffer contained within img_buffer_fmt, in the format specified. Specify using `threadedness` whether to calculate using one thread or multiple, and using `vectorisation` how many pixels to iterate at once
+#[allow(clippy::too_many_arguments)]
+pub fn render_fractal_to<C>(threadedness: Threadedness, vectorisation: Vectorisation, img_buffer_fmt: ImageBufferFormat, fractal_type: FractalType, colour_map: &C, dimensions: (u32, u32), transform: &PlaneTransform<f64>, max_iterations: Option<u32>) where C: ColourMap + Sync + ?Sized {
+	let iterations = compute_iterations(threadedness, vectorisation, fractal_type, dimensions, transform, max_iterations);
+
+	super::colour_iterations_to(threadedness, img_buffer_fmt, &iterations, colour_map);
+}
+
+/// Calculates the escape data of every pixel of `fractal_type`, with given dimensions, and a defined transformation from the image coordinate plane to the complex plane, and the max_iterations is the amount of detail (50-100 being low, >=1000 being high, default 100)
+///
+/// Specify using `threadedness` whether to calculate using one thread or multiple, and using `vectorisation` how many pixels to iterate at once. `Vectorisation::F64x4` gives exactly the same escape data as `Vectorisation::Scalar`. Only the mandelbrot and julia sets are vectorised, and other fractals are calculated as with `Vectorisation::Scalar`
+pub fn compute_iterations(threadedness: Threadedness, vectorisation: Vectorisation, fractal_type: FractalType, dimensions: (u32, u32), transform: &PlaneTransform<f64>, max_iterations: Option<u32>) -> IterationBuffer {
+	// Only z^2 + c is vectorised, so other fractals are calculated a pixel at a time
+	if vectorisation == Vectorisation::Scalar || !matches!(fractal_type, FractalType::MandelbrotSet | FractalType::JuliaSet { .. }) {
+		return super::compute_iterations(threadedness, &fractal_type, dimensions, transform, max_iterations, false);
+	}
+
+	let (width, height) = dimensions;
+	let max_iterations = max_iterations.unwrap_or(fractal::DEFAULT_MAX_ITERATIONS);
+
+	let mut data: Vec<EscapeData> = vec![EscapeData::default(); width as usize * height as usize];
+
+	let mut rows = common::into_rows_mut(&mut data, width, height);
+
+	match threadedness {
+		Threadedness::Singlethreaded => rows.iter_mut().enumerate().for_each(|(y, row)| iterate_row(vectorisation, fractal_type, y as u32, transform, max_iterations, row)),
+		#[cfg(feature = "parallel")]
+		Threadedness::Multithreaded => rows.par_iter_mut().enumerate().for_each(|(y, row)| iterate_row(vectorisation, fractal_type, y as u32, transform, max_iterations, row))
+	}
+
+	IterationBuffer {
+		width,
+		height,
+		max_iterations,
+		data
+	}
+}
+
+/// Calculates the escape data of each pixel in the row `y`, a lane group at a time
+fn iterate_row(vectorisation: Vectorisation, fractal_type: FractalType, y: u32, transform: &PlaneTransform<f64>, max_iterations: u32, row: &mut [EscapeData]) {
+	let lanes = vectorisation.lanes();
+
+	for (i, chunk) in row.chunks_mut(lanes).enumerate() {
+		let first_x = (i * lanes) as u32;
+
+		match vectorisation {
+			Vectorisation::F64x4 => {
+				let (z, c) = lane_values::<4>(fractal_type, first_x, y, chunk.len(), transform);
+				let results = iterate_f64x4(z, c, max_iterations);
+				chunk.copy_from_slice(&results[..chunk.len()]);
+			},
+			Vectorisation::F32x8 => {
+				let (z, c) = lane_values::<8>(fractal_type, first_x, y, chunk.len(), transform);
+				let to_f32 = |v: Complex<f64>| Complex::new(v.re as f32, v.im as f32);
+				let results = iterate_f32x8(z.map(to_f32), c.map(to_f32), max_iterations);
+				chunk.copy_from_slice(&results[..chunk.len()]);
+			},
+			Vectorisation::Scalar => unreachable!()
+		}
+	}
+}
+
+/// Returns the initial z and the constant c of each of the `N` lanes starting from the pixel at (first_x, y), built on the stack. Only `len` pixels are left in the row, so the lanes past them repeat the last pixel
+fn lane_values<const N: usize>(fractal_type: FractalType, first_x: u32, y: u32, len: usize, transform: &PlaneTransform<f64>) -> ([Complex<f64>; N], [Complex<f64>; N]) {
+	let mut z = [Complex::new(0., 0.); N];
+	let mut c = [Complex::new(0., 0.); N];
+
+	for lane in 0..N {
+		let x = first_x + lane.min(len - 1) as u32;
+		let (cx, cy) = transform.transform((x as f64, y as f64));
+		(z[lane], c[lane]) = initial_values(fractal_type, Complex::new(cx, cy));
+	}
+
+	(z, c)
+}
+
+/// Returns the initial z and the constant c of the point `point` for `fractal_type`
+fn initial_values(fractal_type: FractalType, point: Complex<f64>) -> (Complex<f64>, Complex<f64>) {
+	match fractal_type {
+		FractalType::MandelbrotSet => (Complex::new(0., 0.), point),
+		FractalType::JuliaSet { c } => (point, c),
+		_ => unreachable!("only the mandelbrot and julia sets are vectorised")
+	}
+}
+
+/// Iterates z = z^2 + c for 4 lanes at once in double precision, using AVX if the CPU supports it
+fn iterate_f64x4(z: [Complex<f64>; 4], c: [Complex<f64>; 4], max_iterations: u32) -> [EscapeData; 4] {
+	#[cfg(target_arch = "x86_64")]
+	if is_x86_feature_detected!("avx") {
+		// Safety: the CPU supports AVX
+		return unsafe { avx::iterate_f64x4(z, c, max_iterations) };
+	}
+
+	iterate_lanes(z, c, max_iterations)
+}
+
+/// Iterates z = z^2 + c for 8 lanes at once in single precision, using AVX if the CPU supports it
+fn iterate_f32x8(z: [Complex<f32>; 8], c: [Complex<f32>; 8], max_iterations: u32) -> [EscapeData; 8] {
+	#[cfg(target_arch = "x86_64")]
+	if max_iterations <= F32_MAX_ITERATIONS && is_x86_feature_detected!("avx") {
+		// Safety: the CPU supports AVX
+		return unsafe { avx::iterate_f32x8(z, c, max_iterations) };
+	}
+
+	iterate_lanes(z, c, max_iterations)
+}
+
+/// The scalar fallback of the lane group functions, which iterates each lane in turn but otherwise behaves the same
+fn iterate_lanes<T, const N: usize>(mut z: [Complex<T>; N], c: [Complex<T>; N], max_iterations: u32) -> [EscapeData; N] where T: Float {
+	let four = T::from(4.).unwrap();
+	let mut counts = [0; N];
+
+	for _ in 0..max_iterations {
+		let mut active = false;
+
+		for ((z, c), count) in z.iter_mut().zip(c.iter()).zip(counts.iter_mut()) {
+			if z.re * z.re + z.im * z.im <= four {
+				*z = Complex::new(z.re * z.re - z.im * z.im + c.re, (z.re + z.re) * z.im + c.im);
+				*count += 1;
+				active = true;
+			}
+		}
+
+		if !active {
+			break;
+		}
+	}
+
+	let mut results = [EscapeData::default(); N];
+	for ((result, z), count) in results.iter_mut().zip(z).zip(counts) {
+		*result = escape_data(count, max_iterations, z.re.to_f64().unwrap(), z.im.to_f64().unwrap());
+	}
+	results
+}
+
+fn escape_data(iterations: u32, max_iterations: u32, re: f64, im: f64) -> EscapeData {
+	EscapeData {
+		iterations,
+		escaped: iterations < max_iterations,
+		final_z: Complex::new(re, im),
+		derivative: None,
+		short_circuit: None
+	}
+}
+
+/// The lane group functions using AVX intrinsics. A lane stops being updated once it escapes, so its final z and iteration count are the same as iterating it on its own
+#[cfg(target_arch = "x86_64")]
+mod avx {
+	use std::arch::x86_64::*;
+	use num::Complex;
+	use super::{EscapeData, escape_data};
+
+	#[target_feature(enable = "avx")]
+	pub unsafe fn iterate_f64x4(z: [Complex<f64>; 4], c: [Complex<f64>; 4], max_iterations: u32) -> [EscapeData; 4] {
+		let mut zr = _mm256_setr_pd(z[0].re, z[1].re, z[2].re, z[3].re);
+		let mut zi = _mm256_setr_pd(z[0].im, z[1].im, z[2].im, z[3].im);
+		let cr = _mm256_setr_pd(c[0].re, c[1].re, c[2].re, c[3].re);
+		let ci = _mm256_setr_pd(c[0].im, c[1].im, c[2].im, c[3].im);
+		let four = _mm256_set1_pd(4.);
+		let one = _mm256_set1_pd(1.);
+		let mut counts = _mm256_setzero_pd();
+
+		for _ in 0..max_iterations {
+			let zr2 = _mm256_mul_pd(zr, zr);
+			let zi2 = _mm256_mul_pd(zi, zi);
+			let active = _mm256_cmp_pd::<_CMP_LE_OQ>(_mm256_add_pd(zr2, zi2), four);
+			if _mm256_movemask_pd(active) == 0 {
+				break;
+			}
+
+			let new_zr = _mm256_add_pd(_mm256_sub_pd(zr2, zi2), cr);
+			let new_zi = _mm256_add_pd(_mm256_mul_pd(_mm256_add_pd(zr, zr), zi), ci);
+			zr = _mm256_blendv_pd(zr, new_zr, active);
+			zi = _mm256_blendv_pd(zi, new_zi, active);
+			counts = _mm256_add_pd(counts, _mm256_and_pd(active, one));
+		}
+
+		let (mut re, mut im, mut n) = ([0.; 4], [0.; 4], [0.; 4]);
+		_mm256_storeu_pd(re.as_mut_ptr(), zr);
+		_mm256_storeu_pd(im.as_mut_ptr(), zi);
+		_mm256_storeu_pd(n.as_mut_ptr(), counts);
+
+		[0, 1, 2, 3].map(|i| escape_data(n[i] as u32, max_iterations, re[i], im[i]))
+	}
+
+	#[target_feature(enable = "avx")]
+	pub unsafe fn iterate_f32x8(z: [Complex<f32>; 8], c: [Complex<f32>; 8], max_iterations: u32) -> [EscapeData; 8] {
+		let mut zr = _mm256_loadu_ps(z.map(|z| z.re).as_ptr());
+		let mut zi = _mm256_loadu_ps(z.map(|z| z.im).as_ptr());
+		let cr = _mm256_loadu_ps(c.map(|c| c.re).as_ptr());
+		let ci = _mm256_loadu_ps(c.map(|c| c.im).as_ptr());
+		let four = _mm256_set1_ps(4.);
+		let one = _mm256_set1_ps(1.);
+		let mut counts = _mm256_setzero_ps();
+
+		for _ in 0..max_iterations {
+			let zr2 = _mm256_mul_ps(zr, zr);
+			let zi2 = _mm256_mul_ps(zi, zi);
+			let active = _mm256_cmp_ps::<_CMP_LE_OQ>(_mm256_add_ps(zr2, zi2), four);
+			if _mm256_movemask_ps(active) == 0 {
+				break;
+			}
+
+			let new_zr = _mm256_add_ps(_mm256_sub_ps(zr2, zi2), cr);
+			let new_zi = _mm256_add_ps(_mm256_mul_ps(_mm256_add_ps(zr, zr), zi), ci);
+			zr = _mm256_blendv_ps(zr, new_zr, active);
+			zi = _mm256_blendv_ps(zi, new_zi, active);
+			counts = _mm256_add_ps(counts, _mm256_and_ps(active, one));
+		}
+
+		let (mut re, mut im, mut n) = ([0.; 8], [0.; 8], [0.; 8]);
+		_mm256_storeu_ps(re.as_mut_ptr(), zr);
+		_mm256_storeu_ps(im.as_mut_ptr(), zi);
+		_mm256_storeu_ps(n.as_mut_ptr(), counts);
+
+		[0, 1, 2, 3, 4, 5, 6, 7].map(|i| escape_data(n[i] as u32, max_iterations, re[i] as f64, im[i] as f64))
+	}
+}
+
+#[test]
+#[cfg(test)]
+fn test_simd_matches_scalar() {
+	use super::super::julia;
+
+	let dims = (61, 47);
+	let transform = PlaneTransform::new().scale((0.05, 0.05)).base_offset((30., 23.)).transformed_offset((0.5, 0.));
+
+	for fractal_type in [FractalType::MandelbrotSet, FractalType::JuliaSet { c: julia::DEFAULT_C }] {
+		let scalar = compute_iterations(Threadedness::Singlethreaded, Vectorisation::Scalar, fractal_type, dims, &transform, Some(300));
+
+		// Double precision lanes do the same operations in the same order, so should match exactly, including the padded last chunk of each row
+		let f64x4 = compute_iterations(Threadedness::Singlethreaded, Vectorisation::F64x4, fractal_type, dims, &transform, Some(300));
+		assert_eq!(f64x4.data, scalar.data);
+
+		// Single precision lanes can only differ near the boundary of the set
+		let f32x8 = compute_iterations(Threadedness::Singlethreaded, Vectorisation::F32x8, fractal_type, dims, &transform, Some(300));
+		let matching = f32x8.data.iter().zip(&scalar.data).filter(|(a, b)| a.iterations == b.iterations).count();
+		assert!(matching as f64 >= 0.95 * scalar.data.len() as f64);
+	}
+
+	// The scalar fallback gives the same results as the SIMD lanes
+	let z = [Complex::new(0., 0.); 4];
+	let c = [Complex::new(-0.75, 0.1), Complex::new(0.3, 0.5), Complex::new(2., 2.), Complex::new(-0.1, 0.65)];
+	assert_eq!(iterate_lanes(z, c, 1000), iterate_f64x4(z, c, 1000));
+	let c = c.map(|c| Complex::new(c.re as f32, c.im as f32));
+	assert_eq!(iterate_lanes([Complex::new(0., 0.); 4], c, 1000)[..], iterate_f32x8([Complex::new(0., 0.); 8], [c, c].concat().try_into().unwrap(), 1000)[..4]);
+}
@@ -1,24 +1,113 @@
-use super::super::{FractalType, PlaneTransform, ImageBufferFormat, PixelArrayFormat, ChannelArrayFormat};
-use super::super::{common, julia, mandelbrot};
+use super::super::{Fractal, PlaneTransform, Scalar, ImageBufferFormat, PixelArrayFormat, ChannelArrayFormat, IterationBuffer, EscapeData};
+use super::super::{common, fractal};
+use super::super::colouring::{ColourMap, Smooth};
+use super::super::Error;
+use super::super::progress::{RenderOptions, ProgressTracker};
+use super::super::renderer::{Renderer, RenderJob};
+use color_space::Rgb;
 use image::RgbImage;
 
-/// Generates an RGB image of the specified fractal, with given dimensions, and a defined transformation from the image coordinate plane to the complex plane, and the max_iterations is the amount of detail (50-100 being low, >=1000 being high, default 100)
-pub fn generate_fractal_image(fractal_type: FractalType, dimensions: (u32, u32), transform: &PlaneTransform<f64>, max_iterations: Option<u32>) -> RgbImage {
+/// Generates an RGB image of `fractal` coloured with `colour_map`, with given dimensions, and a defined transformation from the image coordinate plane to the complex plane, and the max_iterations is the amount of detail (50-100 being low, >=1000 being high, default 100)
+pub fn generate_fractal_image<T, F, C>(fractal: &F, colour_map: &C, dimensions: (u32, u32), transform: &PlaneTransform<T>, max_iterations: Option<u32>) -> RgbImage where T: Scalar, F: Fractal<T> + ?Sized, C: ColourMap + ?Sized {
 	let (width, height) = dimensions;
 
 	let transform = transform.clone();
 
 	let mut img_buffer: Vec<u8> = vec![0; width as usize * height as usize * 3];
 
-	render_fractal_to(ImageBufferFormat::ChannelArray(ChannelArrayFormat::Rgb8(&mut img_buffer)), fractal_type, dimensions, &transform, max_iterations);
+	render_fractal_to(ImageBufferFormat::ChannelArray(ChannelArrayFormat::Rgb8(&mut img_buffer)), fractal, colour_map, dimensions, &transform, max_iterations);
 
 	RgbImage::from_raw(width, height, img_buffer).unwrap()
 }
 
-/// Generates an RGB image of the specified fractal, with given dimensions, and a defined transformation from the image coordinate plane to the complex plane, and the max_iterations is the amount of detail (50-100 being low, >=1000 being high, default 100)
+/// Generates an RGB image of `fractal` coloured with `colour_map`, with given dimensions, and a defined transformation from the image coordinate plane to the complex plane, and the max_iterations is the amount of detail (50-100 being low, >=1000 being high, default 100)
 ///
 /// The RGB image is written to the buffer contained within img_buffer_fmt, in the format specified
-pub fn render_fractal_to(img_buffer_fmt: ImageBufferFormat, fractal_type: FractalType, dimensions: (u32, u32), transform: &PlaneTransform<f64>, max_iterations: Option<u32>) {
+pub fn render_fractal_to<T, F, C>(img_buffer_fmt: ImageBufferFormat, fractal: &F, colour_map: &C, dimensions: (u32, u32), transform: &PlaneTransform<T>, max_iterations: Option<u32>) where T: Scalar, F: Fractal<T> + ?Sized, C: ColourMap + ?Sized {
+	write_pixels(img_buffer_fmt, dimensions, |x, y| fractal::calculate_pixel(fractal, colour_map, x, y, transform, max_iterations));
+}
+
+/// Same as `render_fractal_to`, but checks the cancellation token of `options` before each row, returning `Error::Cancelled` with the image incomplete if it is cancelled, and reports the number of rows completed to its progress callback
+pub fn render_fractal_to_with_options<T, F, C>(options: &RenderOptions, img_buffer_fmt: ImageBufferFormat, fractal: &F, colour_map: &C, dimensions: (u32, u32), transform: &PlaneTransform<T>, max_iterations: Option<u32>) -> Result<(), Error> where T: Scalar, F: Fractal<T> + ?Sized, C: ColourMap + ?Sized {
+	let (width, height) = dimensions;
+	let mut img_buffer_fmt = img_buffer_fmt;
+	let tracker = ProgressTracker::new(options, height as u64);
+
+	for y in 0..height {
+		tracker.check()?;
+
+		let colours: Vec<Rgb> = (0..width).map(|x| fractal::calculate_pixel(fractal, colour_map, x, y, transform, max_iterations)).collect();
+		common::write_rgb_block_to(&mut img_buffer_fmt, width, (0, y), width, &colours);
+
+		tracker.advance(1);
+	}
+
+	Ok(())
+}
+
+/// Calculates the escape data of every pixel of `fractal`, with given dimensions, and a defined transformation from the image coordinate plane to the complex plane, and the max_iterations is the amount of detail (50-100 being low, >=1000 being high, default 100)
+///
+/// If `track_derivative` is true, the derivative is also calculated for fractals that support it. The returned buffer can be coloured with `colour_iterations_to` or `generate_image_from_iterations`
+pub fn compute_iterations<T, F>(fractal: &F, dimensions: (u32, u32), transform: &PlaneTransform<T>, max_iterations: Option<u32>, track_derivative: bool) -> IterationBuffer where T: Scalar, F: Fractal<T> + ?Sized {
+	let (width, height) = dimensions;
+	let max_iterations = max_iterations.unwrap_or(fractal::DEFAULT_MAX_ITERATIONS);
+
+	let mut data: Vec<EscapeData> = Vec::with_capacity(width as usize * height as usize);
+
+	for y in 0..height {
+		for x in 0..width {
+			data.push(fractal::calculate_escape_data(fractal, x, y, transform, max_iterations, track_derivative));
+		}
+	}
+
+	IterationBuffer {
+		width,
+		height,
+		max_iterations,
+		data
+	}
+}
+
+/// Generates an RGB image from the escape data in `iterations`, mapping each pixel to a colour with `colour_map`
+pub fn generate_image_from_iterations<C>(iterations: &IterationBuffer, colour_map: &C) -> RgbImage where C: ColourMap + ?Sized {
+	let (width, height) = iterations.dimensions();
+
+	let mut img_buffer: Vec<u8> = vec![0; width as usize * height as usize * 3];
+
+	colour_iterations_to(ImageBufferFormat::ChannelArray(ChannelArrayFormat::Rgb8(&mut img_buffer)), iterations, colour_map);
+
+	RgbImage::from_raw(width, height, img_buffer).unwrap()
+}
+
+/// Maps each pixel of the escape data in `iterations` to a colour with `colour_map`
+///
+/// The RGB image is written to the buffer contained within img_buffer_fmt, in the format specified
+pub fn colour_iterations_to<C>(img_buffer_fmt: ImageBufferFormat, iterations: &IterationBuffer, colour_map: &C) where C: ColourMap + ?Sized {
+	write_pixels(img_buffer_fmt, iterations.dimensions(), |x, y| colour_map.colour(iterations.get(x, y), iterations.max_iterations));
+}
+
+/// Renders on the CPU, using one thread
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SinglethreadedRenderer;
+
+impl Renderer for SinglethreadedRenderer {
+	fn render_fractal_to(&self, job: &RenderJob, img_buffer_fmt: ImageBufferFormat) -> Result<(), Error> {
+		self.render_fractal_to_with_options(job, img_buffer_fmt, &RenderOptions::new())
+	}
+
+	fn render_fractal_to_with_options(&self, job: &RenderJob, img_buffer_fmt: ImageBufferFormat, options: &RenderOptions) -> Result<(), Error> {
+		let fractal = job.checked_fractal();
+
+		if job.smooth_colouring {
+			render_fractal_to_with_options(options, img_buffer_fmt, &fractal, &Smooth::for_fractal(job.colour_map, &fractal), job.dimensions, &job.transform, job.max_iterations)
+		} else {
+			render_fractal_to_with_options(options, img_buffer_fmt, &fractal, &job.colour_map, job.dimensions, &job.transform, job.max_iterations)
+		}
+	}
+}
+
+/// Writes the colour returned by `pixel` for each (x, y) to the buffer contained within img_buffer_fmt, in the format specified
+fn write_pixels<P>(img_buffer_fmt: ImageBufferFormat, dimensions: (u32, u32), pixel: P) where P: Fn(u32, u32) -> Rgb {
 	let (width, height) = dimensions;
 
 	match img_buffer_fmt {
@@ -28,10 +117,7 @@ pub fn render_fractal_to(img_buffer_fmt: ImageBufferFormat, fractal_type: Fracta
 
 				rows.iter_mut().enumerate().for_each(|(y, row)| {
 					for x in 0..width {
-						let rgb = match fractal_type {
-							FractalType::MandelbrotSet => mandelbrot::calculate_pixel(x, y as u32, transform, max_iterations),
-							FractalType::JuliaSet => julia::calculate_pixel(x, y as u32, transform, max_iterations)
-						};
+						let rgb = pixel(x, y as u32);
 
 						row[x as usize] = common::to_0rgb_u8(rgb.r as u8, rgb.g as u8, rgb.b as u8);
 					}
@@ -47,10 +133,7 @@ pub fn render_fractal_to(img_buffer_fmt: ImageBufferFormat, fractal_type: Fracta
 
 					for x in 0..(width * 4) {
 						if counter == 0 {
-							let rgb = match fractal_type {
-								FractalType::MandelbrotSet => mandelbrot::calculate_pixel(x / 4, y as u32, transform, max_iterations),
-								FractalType::JuliaSet => julia::calculate_pixel(x / 4, y as u32, transform, max_iterations)
-							};
+							let rgb = pixel(x / 4, y as u32);
 
 							row[x as usize] = 255;
 							row[x as usize + 1] = rgb.r as u8;
@@ -72,10 +155,7 @@ pub fn render_fractal_to(img_buffer_fmt: ImageBufferFormat, fractal_type: Fracta
 
 					for x in 0..(width * 3) {
 						if counter == 0 {
-							let rgb = match fractal_type {
-								FractalType::MandelbrotSet => mandelbrot::calculate_pixel(x / 3, y as u32, transform, max_iterations),
-								FractalType::JuliaSet => julia::calculate_pixel(x / 3, y as u32, transform, max_iterations)
-							};
+							let rgb = pixel(x / 3, y as u32);
 
 							row[x as usize] = rgb.r as u8;
 							row[x as usize + 1] = rgb.g as u8;
@@ -87,7 +167,29 @@ pub fn render_fractal_to(img_buffer_fmt: ImageBufferFormat, fractal_type: Fracta
 						}
 					}
 				});
+			},
+			ChannelArrayFormat::Rgba8(ch_arr) => {
+				let mut rows = common::into_rows_mut(ch_arr, width * 4, height);
+
+				rows.iter_mut().enumerate().for_each(|(y, row)| {
+					let mut counter: u8 = 0;
+
+					for x in 0..(width * 4) {
+						if counter == 0 {
+							let rgb = pixel(x / 4, y as u32);
+
+							row[x as usize] = rgb.r as u8;
+							row[x as usize + 1] = rgb.g as u8;
+							row[x as usize + 2] = rgb.b as u8;
+							row[x as usize + 3] = 255;
+
+							counter = 3;
+						} else {
+							counter -= 1;
+						}
+					}
+				});
 			}
 		}
 	};
-}
\ No newline at end of file
+}
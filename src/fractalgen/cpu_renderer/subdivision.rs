@@ -0,0 +1,311 @@
+use super::Threadedness;
+use super::super::{Fractal, FractalType, PlaneTransform, Scalar, ImageBufferFormat, ChannelArrayFormat, IterationBuffer, EscapeData};
+use super::super::fractal;
+use super::super::colouring::{ColourMap, Smooth};
+use super::super::Error;
+use super::super::progress::{RenderOptions, ProgressTracker};
+use super::super::renderer::{Renderer, RenderJob};
+use std::sync::OnceLock;
+use image::RgbImage;
+use num::Complex;
+
+/// Rectangles with a side of at most this many pixels (including the border) are calculated pixel by pixel rather than subdivided further
+const MIN_SIZE: u32 = 6;
+
+/// Rectangles outside the set with a side of more than this many pixels are subdivided even if their border is uniform, so that a thin filament crossing a large rectangle is found by the borders of the smaller ones
+const MAX_FILL_SIZE: u32 = 32;
+
+/// Inside rectangles outside the set, the pixels this many apart are also checked before the rectangle is filled
+const FILL_SAMPLE_SPACING: u32 = 4;
+
+/// Colouring uses multiple threads if they are available
+#[cfg(feature = "parallel")]
+const THREADEDNESS: Threadedness = Threadedness::Multithreaded;
+#[cfg(not(feature = "parallel"))]
+const THREADEDNESS: Threadedness = Threadedness::Singlethreaded;
+
+/// Generates an RGB image of `fractal` coloured with `colour_map`, with given dimensions, and a defined transformation from the image coordinate plane to the complex plane, and the max_iterations is the amount of detail (50-100 being low, >=1000 being high, default 100)
+pub fn generate_fractal_image<T, F, C>(fractal: &F, colour_map: &C, dimensions: (u32, u32), transform: &PlaneTransform<T>, max_iterations: Option<u32>) -> RgbImage where T: Scalar, F: Fractal<T> + Sync + ?Sized, C: ColourMap + Sync + ?Sized {
+	let (width, height) = dimensions;
+
+	let mut img_buffer: Vec<u8> = vec![0; width as usize * height as usize * 3];
+
+	render_fractal_to(ImageBufferFormat::ChannelArray(ChannelArrayFormat::Rgb8(&mut img_buffer)), fractal, colour_map, dimensions, transform, max_iterations);
+
+	RgbImage::from_raw(width, height, img_buffer).unwrap()
+}
+
+/// Generates an RGB image of `fractal` coloured with `colour_map`, with given dimensions, and a defined transformation from the image coordinate plane to the complex plane, and the max_iterations is the amount of detail (50-100 being low, >=1000 being high, default 100)
+///
+/// The RGB image is written to the buffer contained within img_buffer_fmt, in the format specified
+pub fn render_fractal_to<T, F, C>(img_buffer_fmt: ImageBufferFormat, fractal: &F, colour_map: &C, dimensions: (u32, u32), transform: &PlaneTransform<T>, max_iterations: Option<u32>) where T: Scalar, F: Fractal<T> + Sync + ?Sized, C: ColourMap + Sync + ?Sized {
+	// Without a cancellation token the render always completes
+	let _ = render_fractal_to_with_options(&RenderOptions::new(), img_buffer_fmt, fractal, colour_map, dimensions, transform, max_iterations);
+}
+
+/// Same as `render_fractal_to`, but checks the cancellation token of `options` before each rectangle, returning `Error::Cancelled` if it is cancelled, and reports the number of pixels completed to its progress callback
+///
+/// The buffer is left untouched if the render is cancelled
+pub fn render_fractal_to_with_options<T, F, C>(options: &RenderOptions, img_buffer_fmt: ImageBufferFormat, fractal: &F, colour_map: &C, dimensions: (u32, u32), transform: &PlaneTransform<T>, max_iterations: Option<u32>) -> Result<(), Error> where T: Scalar, F: Fractal<T> + Sync + ?Sized, C: ColourMap + Sync + ?Sized {
+	let iterations = compute_iterations_with_options(options, fractal, dimensions, transform, max_iterations)?;
+
+	super::colour_iterations_to(THREADEDNESS, img_buffer_fmt, &iterations, colour_map);
+
+	Ok(())
+}
+
+/// Calculates the escape data of every pixel of `fractal`, with given dimensions, and a defined transformation from the image coordinate plane to the complex plane, and the max_iterations is the amount of detail (50-100 being low, >=1000 being high, default 100)
+///
+/// Rectangles whose border pixels all have the same number of iterations are filled with the escape data of one of them without calculating their insides. This relies on the level sets of the fractal being connected (see `has_connected_level_sets`), so that a region of another number of iterations inside a rectangle must reach its border. A filament thinner than a pixel could still pass between two border pixels, so rectangles are only filled when that is ruled out:
+/// - Rectangles outside the set are at most `MAX_FILL_SIZE` pixels across, and a grid of their inside pixels must also be uniform
+/// - Rectangles inside the set are only filled if `Fractal::interior_distance` proves that every border pixel is inside the set by more than the distance between neighbouring pixels. The whole border is then inside the set, and so is everything it encloses, as the set has no holes
+///
+/// The iteration counts are then the same as those of `single_threaded::compute_iterations`, so images coloured by escape count are pixel-identical to those of the other CPU renderers, but the final z of filled pixels is not their own. The returned buffer can be coloured with `colour_iterations_to` or `generate_image_from_iterations`
+pub fn compute_iterations<T, F>(fractal: &F, dimensions: (u32, u32), transform: &PlaneTransform<T>, max_iterations: Option<u32>) -> IterationBuffer where T: Scalar, F: Fractal<T> + Sync + ?Sized {
+	// Without a cancellation token every rectangle is calculated
+	compute_iterations_with_options(&RenderOptions::new(), fractal, dimensions, transform, max_iterations).unwrap()
+}
+
+/// Same as `compute_iterations`, but checks the cancellation token of `options` before each rectangle, returning `Error::Cancelled` if it is cancelled, and reports the number of pixels completed to its progress callback
+pub fn compute_iterations_with_options<T, F>(options: &RenderOptions, fractal: &F, dimensions: (u32, u32), transform: &PlaneTransform<T>, max_iterations: Option<u32>) -> Result<IterationBuffer, Error> where T: Scalar, F: Fractal<T> + Sync + ?Sized {
+	let (width, height) = dimensions;
+	let max_iterations = max_iterations.unwrap_or(fractal::DEFAULT_MAX_ITERATIONS);
+	let tracker = ProgressTracker::new(options, width as u64 * height as u64);
+
+	// The furthest apart in the complex plane that neighbouring pixels are
+	let point = |x: f64, y: f64| {
+		let (cx, cy) = transform.transform((T::from_f64(x), T::from_f64(y)));
+		(cx, cy)
+	};
+	let distance = |(ax, ay): (T, T), (bx, by): (T, T)| {
+		let (dx, dy) = (ax - bx, ay - by);
+		(dx * dx + dy * dy).to_f64().sqrt()
+	};
+	let pixel_spacing = distance(point(1., 0.), point(0., 0.)).max(distance(point(0., 1.), point(0., 0.)));
+
+	let grid = Grid {
+		cells: (0..(width as usize * height as usize)).map(|_| OnceLock::new()).collect(),
+		interior_distances: (0..(width as usize * height as usize)).map(|_| OnceLock::new()).collect(),
+		width,
+		max_iterations,
+		pixel_spacing,
+		pixel: |x, y| fractal::calculate_escape_data(fractal, x, y, transform, max_iterations, false),
+		interior_distance: |x, y| {
+			let (cx, cy) = point(x as f64, y as f64);
+			fractal.interior_distance(Complex::new(cx, cy), max_iterations)
+		}
+	};
+
+	if width > 0 && height > 0 {
+		subdivide(&grid, &tracker, (0, 0), (width - 1, height - 1), true);
+	}
+
+	// Rectangles are skipped once the render is cancelled, leaving some pixels unset
+	let data = grid.cells.into_iter().map(|cell| cell.into_inner()).collect::<Option<Vec<EscapeData>>>().ok_or(Error::Cancelled)?;
+
+	Ok(IterationBuffer {
+		width,
+		height,
+		max_iterations,
+		data
+	})
+}
+
+/// Returns whether the level sets of `fractal` (the regions escaping after at least each number of iterations, up to `max_iterations`) are connected, which subdivision relies on
+///
+/// They are for the mandelbrot set, and for the julia sets whose c is in the mandelbrot set (to `max_iterations`), as the preimages of a disc are connected while the orbit of 0 stays inside it. They are not for the other fractal types, e.g. the burning ship
+pub fn has_connected_level_sets(fractal: &FractalType, max_iterations: u32) -> bool {
+	match fractal {
+		FractalType::MandelbrotSet => true,
+		FractalType::JuliaSet { c } => !Fractal::<f64>::iterate(&FractalType::MandelbrotSet, *c, max_iterations).escaped,
+		_ => false
+	}
+}
+
+/// Renders on the CPU by subdividing the image into rectangles and filling those with uniform borders, using multiple threads if the `parallel` feature is enabled
+///
+/// The images are the same as those of the other CPU renderers, see `compute_iterations`. Only fractals whose level sets are connected are subdivided (see `has_connected_level_sets`), and smooth colouring needs the final z of every pixel, so other jobs are rendered pixel by pixel
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SubdivisionRenderer;
+
+impl Renderer for SubdivisionRenderer {
+	fn render_fractal_to(&self, job: &RenderJob, img_buffer_fmt: ImageBufferFormat) -> Result<(), Error> {
+		self.render_fractal_to_with_options(job, img_buffer_fmt, &RenderOptions::new())
+	}
+
+	fn render_fractal_to_with_options(&self, job: &RenderJob, img_buffer_fmt: ImageBufferFormat, options: &RenderOptions) -> Result<(), Error> {
+		let fractal = job.checked_fractal();
+
+		if job.smooth_colouring {
+			super::render_fractal_to_with_options(THREADEDNESS, options, img_buffer_fmt, &fractal, &Smooth::for_fractal(job.colour_map, &fractal), job.dimensions, &job.transform, job.max_iterations)
+		} else if !has_connected_level_sets(&job.fractal, job.max_iterations.unwrap_or(fractal::DEFAULT_MAX_ITERATIONS)) {
+			super::render_fractal_to_with_options(THREADEDNESS, options, img_buffer_fmt, &fractal, &job.colour_map, job.dimensions, &job.transform, job.max_iterations)
+		} else {
+			render_fractal_to_with_options(options, img_buffer_fmt, &fractal, &job.colour_map, job.dimensions, &job.transform, job.max_iterations)
+		}
+	}
+}
+
+/// The escape data of every pixel, each calculated or filled at most once, so that rectangles can be calculated on different threads
+struct Grid<P, D> where P: Fn(u32, u32) -> EscapeData + Sync, D: Fn(u32, u32) -> Option<f64> + Sync {
+	cells: Vec<OnceLock<EscapeData>>,
+	/// The interior distances of the pixels on the borders of rectangles inside the set, each calculated at most once
+	interior_distances: Vec<OnceLock<Option<f64>>>,
+	width: u32,
+	max_iterations: u32,
+	/// The furthest apart in the complex plane that neighbouring pixels are
+	pixel_spacing: f64,
+	pixel: P,
+	interior_distance: D
+}
+
+impl<P, D> Grid<P, D> where P: Fn(u32, u32) -> EscapeData + Sync, D: Fn(u32, u32) -> Option<f64> + Sync {
+	/// Returns the escape data of the pixel at (x, y), calculating it if it hasn't been already
+	fn get(&self, x: u32, y: u32) -> EscapeData {
+		*self.cells[x as usize + y as usize * self.width as usize].get_or_init(|| (self.pixel)(x, y))
+	}
+
+	fn fill(&self, x: u32, y: u32, data: EscapeData) {
+		// Each pixel inside a rectangle belongs to only that rectangle, so is never already set
+		let _ = self.cells[x as usize + y as usize * self.width as usize].set(data);
+	}
+
+	/// Returns whether the pixel at (x, y) is proven to be inside the set by more than the pixel spacing, so that the discs around neighbouring pixels overlap
+	fn is_deep_inside(&self, x: u32, y: u32) -> bool {
+		self.interior_distances[x as usize + y as usize * self.width as usize]
+			.get_or_init(|| (self.interior_distance)(x, y))
+			.is_some_and(|distance| distance > self.pixel_spacing)
+	}
+}
+
+/// Calculates or fills every pixel of the rectangle from `min` to `max` (inclusive), by checking whether it can be filled and otherwise splitting it in two along its longer side. `is_image` is true for the rectangle of the whole image, whose border hasn't been reported as progress by a split
+fn subdivide<P, D>(grid: &Grid<P, D>, tracker: &ProgressTracker, min: (u32, u32), max: (u32, u32), is_image: bool) where P: Fn(u32, u32) -> EscapeData + Sync, D: Fn(u32, u32) -> Option<f64> + Sync {
+	if tracker.check().is_err() {
+		return;
+	}
+
+	let (x0, y0) = min;
+	let (x1, y1) = max;
+	let border = || (x0..=x1).flat_map(move |x| [(x, y0), (x, y1)]).chain((y0..=y1).flat_map(move |y| [(x0, y), (x1, y)]));
+
+	let first = grid.get(x0, y0);
+	// Every border pixel is calculated, as the border is shared with the neighbouring rectangles and the halves of this one
+	let uniform = border().fold(true, |uniform, (x, y)| grid.get(x, y).iterations == first.iterations && uniform);
+
+	let (width, height) = (x1 - x0 + 1, y1 - y0 + 1);
+	if is_image {
+		tracker.advance(width as u64 * height as u64 - (width.saturating_sub(2) as u64 * height.saturating_sub(2) as u64));
+	}
+	let small = x1 - x0 < MIN_SIZE || y1 - y0 < MIN_SIZE;
+
+	let fillable = uniform && if first.iterations < grid.max_iterations {
+		(small || (x1 - x0 <= MAX_FILL_SIZE && y1 - y0 <= MAX_FILL_SIZE))
+			&& ((y0 + FILL_SAMPLE_SPACING)..y1).step_by(FILL_SAMPLE_SPACING as usize)
+				.flat_map(|y| ((x0 + FILL_SAMPLE_SPACING)..x1).step_by(FILL_SAMPLE_SPACING as usize).map(move |x| (x, y)))
+				.all(|(x, y)| grid.get(x, y).iterations == first.iterations)
+	} else {
+		border().all(|(x, y)| grid.is_deep_inside(x, y))
+	};
+
+	if fillable || small {
+		for y in (y0 + 1)..y1 {
+			for x in (x0 + 1)..x1 {
+				if fillable {
+					grid.fill(x, y, first);
+				} else {
+					grid.get(x, y);
+				}
+			}
+		}
+		tracker.advance(width.saturating_sub(2) as u64 * height.saturating_sub(2) as u64);
+	} else if x1 - x0 >= y1 - y0 {
+		// The two halves share the dividing line as part of their borders, which is completed here
+		let xm = (x0 + x1) / 2;
+		for y in (y0 + 1)..y1 {
+			grid.get(xm, y);
+		}
+		tracker.advance(height as u64 - 2);
+		join(|| subdivide(grid, tracker, (x0, y0), (xm, y1), false), || subdivide(grid, tracker, (xm, y0), (x1, y1), false));
+	} else {
+		let ym = (y0 + y1) / 2;
+		for x in (x0 + 1)..x1 {
+			grid.get(x, ym);
+		}
+		tracker.advance(width as u64 - 2);
+		join(|| subdivide(grid, tracker, (x0, y0), (x1, ym), false), || subdivide(grid, tracker, (x0, ym), (x1, y1), false));
+	}
+}
+
+/// Calls `a` and `b`, in parallel if the `parallel` feature is enabled
+fn join<A, B>(a: A, b: B) where A: FnOnce() + Send, B: FnOnce() + Send {
+	#[cfg(feature = "parallel")]
+	rayon::join(a, b);
+	#[cfg(not(feature = "parallel"))]
+	{
+		a();
+		b();
+	}
+}
+
+#[test]
+#[cfg(test)]
+fn test_subdivision_matches_brute_force() {
+	use super::super::Viewport;
+
+	// Views full of filaments thinner than a pixel: seahorse valley, the elephant valley, the edge of a minibrot, and dendritic and connected julia sets
+	let views = [
+		(FractalType::MandelbrotSet, Viewport::new(Complex::new(-0.745, 0.113), 5e-3), 2000),
+		(FractalType::MandelbrotSet, Viewport::new(Complex::new(-0.7436438870371587, 0.131825904205312), 1e-4), 2000),
+		(FractalType::MandelbrotSet, Viewport::new(Complex::new(0.2925, 0.0149), 4e-3), 1000),
+		(FractalType::MandelbrotSet, Viewport::new(Complex::new(-1.76877, 0.00173), 2e-4), 1000),
+		(FractalType::JuliaSet { c: Complex::new(0., 1.) }, Viewport::new(Complex::new(0., 0.), 1.5), 1000),
+		(FractalType::JuliaSet { c: Complex::new(-0.123, 0.745) }, Viewport::new(Complex::new(0.1, 0.1), 0.4), 500)
+	];
+
+	for (fractal, viewport, max_iterations) in views {
+		assert!(has_connected_level_sets(&fractal, max_iterations));
+
+		for dims in [(97, 64), (300, 200)] {
+			let transform = viewport.to_transform(dims);
+			let brute_force = super::single_threaded::compute_iterations(&fractal, dims, &transform, Some(max_iterations), false);
+			let subdivided = compute_iterations(&fractal, dims, &transform, Some(max_iterations));
+
+			assert!(subdivided.data.iter().zip(&brute_force.data).all(|(a, b)| a.iterations == b.iterations));
+		}
+	}
+}
+
+#[test]
+#[cfg(test)]
+fn test_subdivision_fallback_and_progress() {
+	use super::super::{Viewport, CancellationToken, Progress};
+	use super::super::variants::Variant;
+	use super::super::renderer::Backend;
+	use std::sync::Mutex;
+
+	// Fractals whose level sets aren't connected are rendered pixel by pixel
+	for fractal in [FractalType::MandelbrotVariant(Variant::BurningShip), FractalType::Multibrot { exponent: -2. }, FractalType::Multibrot { exponent: 2.5 }, FractalType::JuliaSet { c: Complex::new(0.5, 0.5) }] {
+		assert!(!has_connected_level_sets(&fractal, 1000));
+
+		let job = RenderJob::new(fractal, (97, 64), Viewport::new(Complex::new(-0.5, -0.5), 1.).to_transform((97, 64)));
+		assert_eq!(Backend::CpuSubdivision.generate_fractal_image(&job).unwrap(), Backend::CpuSinglethreaded.generate_fractal_image(&job).unwrap());
+	}
+
+	// Progress is reported in pixels, once for every rectangle
+	let dims = (300, 200);
+	let transform = Viewport::new(Complex::new(-0.745, 0.113), 5e-3).to_transform(dims);
+	let reports: Mutex<Vec<Progress>> = Mutex::new(Vec::new());
+	let on_progress = |progress: &Progress| reports.lock().unwrap().push(*progress);
+	let iterations = compute_iterations_with_options(&RenderOptions::new().on_progress(&on_progress), &FractalType::MandelbrotSet, dims, &transform, Some(1000)).unwrap();
+	assert!(iterations.data.iter().zip(&compute_iterations(&FractalType::MandelbrotSet, dims, &transform, Some(1000)).data).all(|(a, b)| a.iterations == b.iterations));
+	let reports = reports.into_inner().unwrap();
+	assert!(reports.len() > 100);
+	assert!(reports.iter().all(|progress| progress.total == 300 * 200));
+	assert_eq!(reports.iter().map(|progress| progress.completed).max(), Some(300 * 200));
+
+	// A token cancelled during the render stops it
+	let token = CancellationToken::new();
+	let cancel_after_first = |_: &Progress| token.cancel();
+	let options = RenderOptions::new().cancellation(&token).on_progress(&cancel_after_first);
+	assert!(matches!(compute_iterations_with_options(&options, &FractalType::MandelbrotSet, dims, &transform, Some(1000)), Err(Error::Cancelled)));
+}
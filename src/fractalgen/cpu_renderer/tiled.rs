@@ -0,0 +1,354 @@
+use super::super::{Fractal, PlaneTransform, Scalar, ImageBufferFormat, ChannelArrayFormat, IterationBuffer, EscapeData};
+use super::super::{common, fractal};
+use super::super::colouring::{ColourMap, Smooth};
+use super::super::Error;
+use super::super::progress::{RenderOptions, ProgressTracker};
+use super::super::renderer::{Renderer, RenderJob};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use image::RgbImage;
+use rayon::ThreadPool;
+
+/// The default width and height of tiles in pixels
+pub const DEFAULT_TILE_SIZE: u32 = 64;
+
+/// The order in which tiles are started. Each thread takes the next tile in the order when it finishes one, so tiles are finished in roughly this order
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TileOrder {
+	/// Left to right, top to bottom
+	RowMajor,
+	/// Outwards from the centre of the image in a square spiral, so the middle of the image is finished first
+	Spiral,
+	/// Along a Hilbert curve, so consecutive tiles are next to each other
+	Hilbert
+}
+
+/// A rectangle of pixels, with its top left corner at (x, y)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Tile {
+	pub x: u32,
+	pub y: u32,
+	pub width: u32,
+	pub height: u32
+}
+
+impl Tile {
+	pub fn new(x: u32, y: u32, width: u32, height: u32) -> Self {
+		Tile { x, y, width, height }
+	}
+
+	/// Returns the part of this rectangle that is inside an image with the given dimensions
+	fn clamped(&self, dimensions: (u32, u32)) -> Self {
+		let x = self.x.min(dimensions.0);
+		let y = self.y.min(dimensions.1);
+
+		Tile {
+			x,
+			y,
+			width: self.width.min(dimensions.0 - x),
+			height: self.height.min(dimensions.1 - y)
+		}
+	}
+}
+
+/// How to split a render into tiles and which threads to render them on
+#[derive(Clone, Copy, Debug)]
+pub struct TileOptions<'a> {
+	/// The width and height of tiles in pixels, except those at the right and bottom edges which may be smaller
+	pub tile_size: u32,
+	pub order: TileOrder,
+	/// The thread pool to render on, or None for the global rayon thread pool
+	pub thread_pool: Option<&'a ThreadPool>,
+	/// The most threads of the thread pool to render on at once, or None for all of them
+	pub num_threads: Option<usize>,
+	/// The rectangle of the image to render, or None for all of it. Pixels outside it are left as they are
+	pub region: Option<Tile>
+}
+
+impl<'a> TileOptions<'a> {
+	pub fn new() -> Self {
+		TileOptions {
+			tile_size: DEFAULT_TILE_SIZE,
+			order: TileOrder::Spiral,
+			thread_pool: None,
+			num_threads: None,
+			region: None
+		}
+	}
+
+	/// Builder-style method
+	pub fn tile_size(mut self, tile_size: u32) -> Self {
+		self.tile_size = tile_size;
+		self
+	}
+
+	/// Builder-style method
+	pub fn order(mut self, order: TileOrder) -> Self {
+		self.order = order;
+		self
+	}
+
+	/// Builder-style method
+	pub fn thread_pool(mut self, thread_pool: &'a ThreadPool) -> Self {
+		self.thread_pool = Some(thread_pool);
+		self
+	}
+
+	/// Builder-style method
+	pub fn num_threads(mut self, num_threads: usize) -> Self {
+		self.num_threads = Some(num_threads);
+		self
+	}
+
+	/// Builder-style method
+	pub fn region(mut self, region: Tile) -> Self {
+		self.region = Some(region);
+		self
+	}
+}
+
+impl Default for TileOptions<'_> {
+	fn default() -> Self {
+		TileOptions::new()
+	}
+}
+
+/// Generates an RGB image of `fractal` coloured with `colour_map`, with given dimensions, and a defined transformation from the image coordinate plane to the complex plane, and the max_iterations is the amount of detail (50-100 being low, >=1000 being high, default 100)
+///
+/// The image is rendered in tiles as specified by `options`
+pub fn generate_fractal_image<T, F, C>(options: &TileOptions, fractal: &F, colour_map: &C, dimensions: (u32, u32), transform: &PlaneTransform<T>, max_iterations: Option<u32>) -> RgbImage where T: Scalar, F: Fractal<T> + Sync + ?Sized, C: ColourMap + Sync + ?Sized {
+	let (width, height) = dimensions;
+
+	let mut img_buffer: Vec<u8> = vec![0; width as usize * height as usize * 3];
+
+	render_fractal_to(options, ImageBufferFormat::ChannelArray(ChannelArrayFormat::Rgb8(&mut img_buffer)), fractal, colour_map, dimensions, transform, max_iterations);
+
+	RgbImage::from_raw(width, height, img_buffer).unwrap()
+}
+
+/// Generates an RGB image of `fractal` coloured with `colour_map`, with given dimensions, and a defined transformation from the image coordinate plane to the complex plane, and the max_iterations is the amount of detail (50-100 being low, >=1000 being high, default 100)
+///
+/// The RGB image is written to the buffer contained within img_buffer_fmt, in the format specified, one tile at a time as specified by `options`
+pub fn render_fractal_to<T, F, C>(options: &TileOptions, img_buffer_fmt: ImageBufferFormat, fractal: &F, colour_map: &C, dimensions: (u32, u32), transform: &PlaneTransform<T>, max_iterations: Option<u32>) where T: Scalar, F: Fractal<T> + Sync + ?Sized, C: ColourMap + Sync + ?Sized {
+	// Without a cancellation token the render always completes
+	let _ = render_fractal_to_with_options(options, &RenderOptions::new(), img_buffer_fmt, fractal, colour_map, dimensions, transform, max_iterations);
+}
+
+/// Same as `render_fractal_to`, but checks the cancellation token of `render_options` before each tile, returning `Error::Cancelled` with the image incomplete if it is cancelled, and reports the number of tiles completed to its progress callback
+#[allow(clippy::too_many_arguments)]
+pub fn render_fractal_to_with_options<T, F, C>(options: &TileOptions, render_options: &RenderOptions, img_buffer_fmt: ImageBufferFormat, fractal: &F, colour_map: &C, dimensions: (u32, u32), transform: &PlaneTransform<T>, max_iterations: Option<u32>) -> Result<(), Error> where T: Scalar, F: Fractal<T> + Sync + ?Sized, C: ColourMap + Sync + ?Sized {
+	let mut img_buffer_fmt = img_buffer_fmt;
+
+	for_each_tile(options, render_options, dimensions, |x, y| fractal::calculate_pixel(fractal, colour_map, x, y, transform, max_iterations), |tile, colours| {
+		common::write_rgb_block_to(&mut img_buffer_fmt, dimensions.0, (tile.x, tile.y), tile.width, &colours);
+	})
+}
+
+/// Calculates the escape data of every pixel of `fractal`, with given dimensions, and a defined transformation from the image coordinate plane to the complex plane, and the max_iterations is the amount of detail (50-100 being low, >=1000 being high, default 100)
+///
+/// If `track_derivative` is true, the derivative is also calculated for fractals that support it. The pixels are calculated in tiles as specified by `options`, and those outside its region are left as the default escape data
+pub fn compute_iterations<T, F>(options: &TileOptions, fractal: &F, dimensions: (u32, u32), transform: &PlaneTransform<T>, max_iterations: Option<u32>, track_derivative: bool) -> IterationBuffer where T: Scalar, F: Fractal<T> + Sync + ?Sized {
+	let (width, height) = dimensions;
+	let max_iterations = max_iterations.unwrap_or(fractal::DEFAULT_MAX_ITERATIONS);
+
+	let mut data: Vec<EscapeData> = vec![EscapeData::default(); width as usize * height as usize];
+
+	// Without a cancellation token every tile is calculated
+	let _ = for_each_tile(options, &RenderOptions::new(), dimensions, |x, y| fractal::calculate_escape_data(fractal, x, y, transform, max_iterations, track_derivative), |tile, tile_data| {
+		for (row, tile_row) in tile_data.chunks_exact(tile.width as usize).enumerate() {
+			let start = tile.x as usize + (tile.y as usize + row) * width as usize;
+			data[start..(start + tile.width as usize)].copy_from_slice(tile_row);
+		}
+	});
+
+	IterationBuffer {
+		width,
+		height,
+		max_iterations,
+		data
+	}
+}
+
+/// Returns the tiles of size `tile_size` covering the image with the given dimensions (or only `region` of it), in the given order
+pub fn tiles(dimensions: (u32, u32), region: Option<Tile>, tile_size: u32, order: TileOrder) -> Vec<Tile> {
+	let region = region.unwrap_or(Tile::new(0, 0, dimensions.0, dimensions.1)).clamped(dimensions);
+	let tile_size = tile_size.max(1);
+
+	let columns = region.width.div_ceil(tile_size);
+	let rows = region.height.div_ceil(tile_size);
+
+	let mut indices: Vec<(u32, u32)> = (0..rows).flat_map(|row| (0..columns).map(move |column| (column, row))).collect();
+
+	match order {
+		TileOrder::RowMajor => (),
+		TileOrder::Spiral => {
+			// Sort by the square ring around the centre tile, and then by the angle within that ring
+			let centre = ((columns as f64 - 1.) / 2., (rows as f64 - 1.) / 2.);
+			let key = |&(column, row): &(u32, u32)| {
+				let (dx, dy) = (column as f64 - centre.0, row as f64 - centre.1);
+				(dx.abs().max(dy.abs()).round(), dy.atan2(dx))
+			};
+			indices.sort_by(|a, b| key(a).partial_cmp(&key(b)).unwrap());
+		},
+		TileOrder::Hilbert => {
+			let n = columns.max(rows).next_power_of_two();
+			indices.sort_by_key(|&(column, row)| hilbert_index(n, column, row));
+		}
+	}
+
+	indices.into_iter().map(|(column, row)| {
+		Tile::new(region.x + column * tile_size, region.y + row * tile_size, tile_size, tile_size).clamped((region.x + region.width, region.y + region.height))
+	}).collect()
+}
+
+/// Renders on the CPU with multiple threads, one tile at a time as specified by its options
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TiledRenderer<'a> {
+	pub options: TileOptions<'a>
+}
+
+impl<'a> TiledRenderer<'a> {
+	pub fn new(options: TileOptions<'a>) -> Self {
+		TiledRenderer { options }
+	}
+}
+
+impl Renderer for TiledRenderer<'_> {
+	fn render_fractal_to(&self, job: &RenderJob, img_buffer_fmt: ImageBufferFormat) -> Result<(), Error> {
+		self.render_fractal_to_with_options(job, img_buffer_fmt, &RenderOptions::new())
+	}
+
+	fn render_fractal_to_with_options(&self, job: &RenderJob, img_buffer_fmt: ImageBufferFormat, render_options: &RenderOptions) -> Result<(), Error> {
+		let fractal = job.checked_fractal();
+
+		if job.smooth_colouring {
+			render_fractal_to_with_options(&self.options, render_options, img_buffer_fmt, &fractal, &Smooth::for_fractal(job.colour_map, &fractal), job.dimensions, &job.transform, job.max_iterations)
+		} else {
+			render_fractal_to_with_options(&self.options, render_options, img_buffer_fmt, &fractal, &job.colour_map, job.dimensions, &job.transform, job.max_iterations)
+		}
+	}
+}
+
+/// Calculates `pixel` for every pixel of each tile in parallel, passing the results of each tile (in row order) to `write` as soon as it is finished
+///
+/// Each thread takes the next tile from a shared counter, so threads that get fast tiles go on to take more of them. Only `write` is called on one thread at a time. Returns `Error::Cancelled` if the render was cancelled before every tile was calculated
+fn for_each_tile<R, P, W>(options: &TileOptions, render_options: &RenderOptions, dimensions: (u32, u32), pixel: P, write: W) -> Result<(), Error> where R: Send, P: Fn(u32, u32) -> R + Sync, W: FnMut(Tile, Vec<R>) + Send {
+	let tiles = tiles(dimensions, options.region, options.tile_size, options.order);
+	let next = AtomicUsize::new(0);
+	let write = Mutex::new(write);
+	let tracker = ProgressTracker::new(render_options, tiles.len() as u64);
+	let cancelled = AtomicBool::new(false);
+
+	let worker = || {
+		while let Some(&tile) = tiles.get(next.fetch_add(1, Ordering::Relaxed)) {
+			if tracker.check().is_err() {
+				cancelled.store(true, Ordering::Relaxed);
+				break;
+			}
+
+			let mut results = Vec::with_capacity(tile.width as usize * tile.height as usize);
+			for y in tile.y..(tile.y + tile.height) {
+				for x in tile.x..(tile.x + tile.width) {
+					results.push(pixel(x, y));
+				}
+			}
+
+			(write.lock().unwrap())(tile, results);
+			tracker.advance(1);
+		}
+	};
+
+	let run = || {
+		let num_threads = options.num_threads.unwrap_or(rayon::current_num_threads()).clamp(1, tiles.len().max(1));
+		rayon::scope(|scope| {
+			for _ in 0..num_threads {
+				scope.spawn(|_| worker());
+			}
+		});
+	};
+
+	match options.thread_pool {
+		Some(thread_pool) => thread_pool.install(run),
+		None => run()
+	}
+
+	if cancelled.load(Ordering::Relaxed) {
+		Err(Error::Cancelled)
+	} else {
+		Ok(())
+	}
+}
+
+/// Returns the distance along a Hilbert curve filling an n by n grid (where n is a power of 2) of the cell (x, y)
+fn hilbert_index(n: u32, x: u32, y: u32) -> u64 {
+	let (mut x, mut y) = (x, y);
+	let mut index = 0;
+
+	let mut s = n / 2;
+	while s > 0 {
+		let rx = (x & s > 0) as u32;
+		let ry = (y & s > 0) as u32;
+		index += s as u64 * s as u64 * ((3 * rx) ^ ry) as u64;
+
+		// Rotate the quadrant so that the curve within it is in the standard orientation
+		if ry == 0 {
+			if rx == 1 {
+				x = n - 1 - x;
+				y = n - 1 - y;
+			}
+			std::mem::swap(&mut x, &mut y);
+		}
+
+		s /= 2;
+	}
+
+	index
+}
+
+#[test]
+#[cfg(test)]
+fn test_tiled_matches_single_threaded() {
+	use super::super::FractalType;
+	use super::super::colouring::ColourMapType;
+	use super::super::PixelArrayFormat;
+
+	let dims = (75, 41);
+
+	// Every pixel is in exactly one tile, whatever the order
+	for order in [TileOrder::RowMajor, TileOrder::Spiral, TileOrder::Hilbert] {
+		let mut counts = vec![0; dims.0 as usize * dims.1 as usize];
+		for tile in tiles(dims, None, 16, order) {
+			for y in tile.y..(tile.y + tile.height) {
+				for x in tile.x..(tile.x + tile.width) {
+					counts[x as usize + y as usize * dims.0 as usize] += 1;
+				}
+			}
+		}
+		assert!(counts.iter().all(|&count| count == 1));
+	}
+
+	// The spiral starts at the centre
+	let first = tiles(dims, None, 16, TileOrder::Spiral)[0];
+	assert!(first.x <= dims.0 / 2 && dims.0 / 2 < first.x + first.width && first.y <= dims.1 / 2 && dims.1 / 2 < first.y + first.height);
+
+	let transform = PlaneTransform::new().scale((0.04, 0.04)).base_offset((50., 20.));
+	let pool = rayon::ThreadPoolBuilder::new().num_threads(2).build().unwrap();
+	let options = TileOptions::new().tile_size(7).order(TileOrder::Hilbert).thread_pool(&pool);
+
+	let expected = super::single_threaded::generate_fractal_image(&FractalType::MandelbrotSet, &ColourMapType::Hue, dims, &transform, Some(300));
+	assert_eq!(generate_fractal_image(&options, &FractalType::MandelbrotSet, &ColourMapType::Hue, dims, &transform, Some(300)), expected);
+
+	let iterations = compute_iterations(&options.num_threads(1), &FractalType::MandelbrotSet, dims, &transform, Some(300), false);
+	assert_eq!(iterations.data, super::single_threaded::compute_iterations(&FractalType::MandelbrotSet, dims, &transform, Some(300), false).data);
+
+	// Only the region is written
+	let mut argb = vec![0u32; dims.0 as usize * dims.1 as usize];
+	render_fractal_to(&options.region(Tile::new(10, 5, 20, 100)), ImageBufferFormat::PixelArray(PixelArrayFormat::Argb32(&mut argb)), &FractalType::MandelbrotSet, &ColourMapType::Hue, dims, &transform, Some(300));
+	for y in 0..dims.1 {
+		for x in 0..dims.0 {
+			let [r, g, b] = expected.get_pixel(x, y).0;
+			let in_region = (10..30).contains(&x) && y >= 5;
+			assert_eq!(argb[x as usize + y as usize * dims.0 as usize], if in_region { common::to_0rgb_u8(r, g, b) } else { 0 });
+		}
+	}
+}
@@ -0,0 +1,217 @@
+//! This module contains the `DoubleDouble` struct, a floating point number represented as the unevaluated sum of two f64s, giving about 106 bits (32 decimal digits) of precision
+//!
+//! It implements `Scalar`, so can be used with `PlaneTransform` and the CPU renderers to render zooms too deep for f64 directly, without perturbation. It is much faster than `BigFloat` as it is `Copy` and never allocates, but has a fixed precision, so is only exact down to radii of about 1e-20
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::{Add, Sub, Mul, Div, Neg, AddAssign, SubAssign, MulAssign};
+use num::{Zero, One};
+use super::common::Scalar;
+use super::bigfloat::BigFloat;
+
+/// A number with the value hi + lo, where |lo| is at most half an ulp of hi
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DoubleDouble {
+	pub hi: f64,
+	pub lo: f64
+}
+
+/// Returns a + b and the rounding error of that addition
+fn two_sum(a: f64, b: f64) -> (f64, f64) {
+	let s = a + b;
+	let bb = s - a;
+	(s, (a - (s - bb)) + (b - bb))
+}
+
+/// Same as `two_sum`, but only correct if |a| >= |b|
+fn quick_two_sum(a: f64, b: f64) -> (f64, f64) {
+	let s = a + b;
+	(s, b - (s - a))
+}
+
+/// Splits a into two halves of 26 bits each, so that products of the halves are exact
+fn split(a: f64) -> (f64, f64) {
+	let t = 134217729. * a; // 2^27 + 1
+	let hi = t - (t - a);
+	(hi, a - hi)
+}
+
+/// Returns a * b and the rounding error of that multiplication
+fn two_prod(a: f64, b: f64) -> (f64, f64) {
+	let p = a * b;
+	let (ah, al) = split(a);
+	let (bh, bl) = split(b);
+	(p, ((ah * bh - p) + ah * bl + al * bh) + al * bl)
+}
+
+impl DoubleDouble {
+	pub const fn new(hi: f64, lo: f64) -> Self {
+		DoubleDouble { hi, lo }
+	}
+
+	/// Converts `value`, rounding it to about 106 bits
+	pub fn from_bigfloat(value: &BigFloat) -> Self {
+		let hi = value.to_f64();
+		let lo = (value - &BigFloat::from_f64(hi, value.precision()).unwrap()).to_f64();
+		let (hi, lo) = quick_two_sum(hi, lo);
+		DoubleDouble { hi, lo }
+	}
+
+	/// Parses a decimal number such as "-0.743643887037158704752191506114774" with its full precision. Returns None if `s` is not a valid decimal number
+	pub fn parse(s: &str) -> Option<Self> {
+		BigFloat::parse(s, 128).map(|value| DoubleDouble::from_bigfloat(&value))
+	}
+
+	pub fn abs(self) -> Self {
+		if self.hi < 0. { -self } else { self }
+	}
+}
+
+impl Scalar for DoubleDouble {
+	fn from_f64(value: f64) -> Self {
+		DoubleDouble { hi: value, lo: 0. }
+	}
+
+	fn to_f64(self) -> f64 {
+		self.hi + self.lo
+	}
+}
+
+impl Add for DoubleDouble {
+	type Output = DoubleDouble;
+
+	fn add(self, other: DoubleDouble) -> DoubleDouble {
+		let (s1, s2) = two_sum(self.hi, other.hi);
+		let (t1, t2) = two_sum(self.lo, other.lo);
+		let (s1, s2) = quick_two_sum(s1, s2 + t1);
+		let (hi, lo) = quick_two_sum(s1, s2 + t2);
+		DoubleDouble { hi, lo }
+	}
+}
+
+impl Sub for DoubleDouble {
+	type Output = DoubleDouble;
+
+	fn sub(self, other: DoubleDouble) -> DoubleDouble {
+		self + -other
+	}
+}
+
+impl Mul for DoubleDouble {
+	type Output = DoubleDouble;
+
+	fn mul(self, other: DoubleDouble) -> DoubleDouble {
+		let (p1, p2) = two_prod(self.hi, other.hi);
+		let (hi, lo) = quick_two_sum(p1, p2 + (self.hi * other.lo + self.lo * other.hi));
+		DoubleDouble { hi, lo }
+	}
+}
+
+impl Div<f64> for DoubleDouble {
+	type Output = DoubleDouble;
+
+	fn div(self, other: f64) -> DoubleDouble {
+		// Long division: the quotient of the high parts, then a correction from the remainder
+		let q1 = self.hi / other;
+		let (p1, p2) = two_prod(q1, other);
+		let (s, e) = two_sum(self.hi, -p1);
+		let q2 = (s + (e - p2 + self.lo)) / other;
+		let (hi, lo) = quick_two_sum(q1, q2);
+		DoubleDouble { hi, lo }
+	}
+}
+
+impl Neg for DoubleDouble {
+	type Output = DoubleDouble;
+
+	fn neg(self) -> DoubleDouble {
+		DoubleDouble { hi: -self.hi, lo: -self.lo }
+	}
+}
+
+impl AddAssign for DoubleDouble {
+	fn add_assign(&mut self, other: DoubleDouble) {
+		*self = *self + other;
+	}
+}
+
+impl SubAssign for DoubleDouble {
+	fn sub_assign(&mut self, other: DoubleDouble) {
+		*self = *self - other;
+	}
+}
+
+impl MulAssign for DoubleDouble {
+	fn mul_assign(&mut self, other: DoubleDouble) {
+		*self = *self * other;
+	}
+}
+
+impl Zero for DoubleDouble {
+	fn zero() -> Self {
+		DoubleDouble { hi: 0., lo: 0. }
+	}
+
+	fn is_zero(&self) -> bool {
+		self.hi == 0.
+	}
+}
+
+impl One for DoubleDouble {
+	fn one() -> Self {
+		DoubleDouble { hi: 1., lo: 0. }
+	}
+}
+
+impl PartialEq for DoubleDouble {
+	fn eq(&self, other: &DoubleDouble) -> bool {
+		self.hi == other.hi && self.lo == other.lo
+	}
+}
+
+impl PartialOrd for DoubleDouble {
+	fn partial_cmp(&self, other: &DoubleDouble) -> Option<Ordering> {
+		match self.hi.partial_cmp(&other.hi) {
+			Some(Ordering::Equal) => self.lo.partial_cmp(&other.lo),
+			ordering => ordering
+		}
+	}
+}
+
+impl From<f64> for DoubleDouble {
+	fn from(value: f64) -> Self {
+		DoubleDouble::from_f64(value)
+	}
+}
+
+impl fmt::Display for DoubleDouble {
+	/// Only shows the value to f64 precision
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}", self.to_f64())
+	}
+}
+
+#[test]
+#[cfg(test)]
+fn test_double_double_arithmetic() {
+	// 1 + 1e-20 is not representable as an f64, but is as a double-double
+	let a = DoubleDouble::from_f64(1.) + DoubleDouble::from_f64(1e-20);
+	assert_eq!(a, DoubleDouble::new(1., 1e-20));
+	assert_eq!((a - DoubleDouble::one()).to_f64(), 1e-20);
+
+	// (1 + 1e-20)^2 = 1 + 2e-20 + 1e-40, where the last term is below the precision
+	let sq = a * a;
+	assert_eq!(sq.hi, 1.);
+	assert!((sq.lo - 2e-20).abs() < 1e-34);
+
+	// One third has an error of about 1e-33 rather than 1e-17
+	let third = DoubleDouble::one() / 3.;
+	let error = (third * DoubleDouble::from_f64(3.) - DoubleDouble::one()).abs();
+	assert!(error.to_f64() < 1e-31);
+
+	// Parsing keeps digits beyond the precision of f64
+	let parsed = DoubleDouble::parse("0.1000000000000000000000000000001").unwrap();
+	let tenth = DoubleDouble::parse("0.1").unwrap();
+	assert!(((parsed - tenth).to_f64() - 1e-31).abs() < 1e-32);
+	assert!(parsed > tenth && -parsed < DoubleDouble::zero());
+}
@@ -0,0 +1,275 @@
+//! This module contains the `Error` enum, the error type returned by the fallible parts of the library, such as rendering on the GPU or a render being cancelled
+//!
+//! Errors from vulkano are wrapped so that callers can match on what went wrong (e.g. no Vulkan device being available) without depending on vulkano themselves
+
+use std::fmt;
+#[cfg(feature = "gpu")]
+use vulkano::{
+	OomError,
+	instance::InstanceCreationError,
+	device::DeviceCreationError,
+	shader::ShaderCreationError,
+	pipeline::compute::ComputePipelineCreationError,
+	descriptor_set::DescriptorSetCreationError,
+	image::{ImageCreationError, view::ImageViewCreationError},
+	memory::DeviceMemoryAllocError,
+	buffer::cpu_access::ReadLockError,
+	command_buffer::{BuildError, CommandBufferExecError, CopyBufferImageError, DispatchError},
+	sync::FlushError
+};
+
+/// The errors that can occur while rendering
+#[derive(Debug)]
+pub enum Error {
+	/// The render was stopped early by its cancellation token, so the image is incomplete
+	Cancelled,
+	/// The Vulkan instance could not be created, usually because no Vulkan implementation is installed
+	#[cfg(feature = "gpu")]
+	InstanceCreation(InstanceCreationError),
+	/// No Vulkan device with a queue that supports compute operations was found
+	#[cfg(feature = "gpu")]
+	NoComputeDevice,
+	#[cfg(feature = "gpu")]
+	DeviceCreation(DeviceCreationError),
+	/// The device did not provide a queue for the queue family that was requested
+	#[cfg(feature = "gpu")]
+	NoQueue,
+	#[cfg(feature = "gpu")]
+	ShaderCreation(ShaderCreationError),
+	/// The shader does not contain exactly one entry point with the given name
+	#[cfg(feature = "gpu")]
+	ShaderEntryPointNotFound(String),
+	#[cfg(feature = "gpu")]
+	ComputePipelineCreation(ComputePipelineCreationError),
+	/// The compute pipeline has no descriptor set layout to bind data to
+	#[cfg(feature = "gpu")]
+	DescriptorSetLayoutNotFound,
+	#[cfg(feature = "gpu")]
+	DescriptorSetCreation(DescriptorSetCreationError),
+	#[cfg(feature = "gpu")]
+	ImageCreation(ImageCreationError),
+	#[cfg(feature = "gpu")]
+	ImageViewCreation(ImageViewCreationError),
+	#[cfg(feature = "gpu")]
+	BufferCreation(DeviceMemoryAllocError),
+	/// Ran out of host or device memory
+	#[cfg(feature = "gpu")]
+	OutOfMemory(OomError),
+	#[cfg(feature = "gpu")]
+	Dispatch(DispatchError),
+	#[cfg(feature = "gpu")]
+	CopyBufferImage(CopyBufferImageError),
+	#[cfg(feature = "gpu")]
+	CommandBufferBuild(BuildError),
+	/// `VkComputeOperation::execute` was called before `VkComputeOperation::build`
+	#[cfg(feature = "gpu")]
+	CommandBufferNotBuilt,
+	#[cfg(feature = "gpu")]
+	CommandBufferExec(CommandBufferExecError),
+	/// Submitting the command buffer or waiting for it to complete failed
+	#[cfg(feature = "gpu")]
+	Flush(FlushError),
+	#[cfg(feature = "gpu")]
+	BufferRead(ReadLockError)
+}
+
+impl Error {
+	/// Returns true if the error was caused by no usable Vulkan implementation or device being available, as opposed to an error in the middle of rendering
+	#[cfg(feature = "gpu")]
+	pub fn is_device_unavailable(&self) -> bool {
+		matches!(self, Error::InstanceCreation(_) | Error::NoComputeDevice | Error::DeviceCreation(_) | Error::NoQueue)
+	}
+}
+
+impl fmt::Display for Error {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Error::Cancelled => write!(f, "the render was cancelled"),
+			#[cfg(feature = "gpu")]
+			Error::InstanceCreation(err) => write!(f, "failed to create Vulkan instance: {}", err),
+			#[cfg(feature = "gpu")]
+			Error::NoComputeDevice => write!(f, "no Vulkan device that supports compute operations was found"),
+			#[cfg(feature = "gpu")]
+			Error::DeviceCreation(err) => write!(f, "failed to create Vulkan device: {}", err),
+			#[cfg(feature = "gpu")]
+			Error::NoQueue => write!(f, "the Vulkan device did not provide a queue"),
+			#[cfg(feature = "gpu")]
+			Error::ShaderCreation(err) => write!(f, "failed to create shader: {}", err),
+			#[cfg(feature = "gpu")]
+			Error::ShaderEntryPointNotFound(name) => write!(f, "entry point \"{}\" not found or multiple instances found", name),
+			#[cfg(feature = "gpu")]
+			Error::ComputePipelineCreation(err) => write!(f, "failed to create compute pipeline: {}", err),
+			#[cfg(feature = "gpu")]
+			Error::DescriptorSetLayoutNotFound => write!(f, "the compute pipeline has no descriptor set layout"),
+			#[cfg(feature = "gpu")]
+			Error::DescriptorSetCreation(err) => write!(f, "failed to create descriptor set: {}", err),
+			#[cfg(feature = "gpu")]
+			Error::ImageCreation(err) => write!(f, "failed to create image: {}", err),
+			#[cfg(feature = "gpu")]
+			Error::ImageViewCreation(err) => write!(f, "failed to create image view: {}", err),
+			#[cfg(feature = "gpu")]
+			Error::BufferCreation(err) => write!(f, "failed to create buffer: {}", err),
+			#[cfg(feature = "gpu")]
+			Error::OutOfMemory(err) => write!(f, "out of memory: {}", err),
+			#[cfg(feature = "gpu")]
+			Error::Dispatch(err) => write!(f, "failed to add the dispatch command to the command buffer: {}", err),
+			#[cfg(feature = "gpu")]
+			Error::CopyBufferImage(err) => write!(f, "failed to add the copy command to the command buffer: {}", err),
+			#[cfg(feature = "gpu")]
+			Error::CommandBufferBuild(err) => write!(f, "failed to build command buffer: {}", err),
+			#[cfg(feature = "gpu")]
+			Error::CommandBufferNotBuilt => write!(f, "command buffer was not built"),
+			#[cfg(feature = "gpu")]
+			Error::CommandBufferExec(err) => write!(f, "failed to send command buffer to the GPU for execution: {}", err),
+			#[cfg(feature = "gpu")]
+			Error::Flush(err) => write!(f, "failed to flush or wait for the GPU: {}", err),
+			#[cfg(feature = "gpu")]
+			Error::BufferRead(err) => write!(f, "failed to read buffer: {}", err)
+		}
+	}
+}
+
+impl std::error::Error for Error {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		match self {
+			#[cfg(feature = "gpu")]
+			Error::InstanceCreation(err) => Some(err),
+			#[cfg(feature = "gpu")]
+			Error::DeviceCreation(err) => Some(err),
+			#[cfg(feature = "gpu")]
+			Error::ShaderCreation(err) => Some(err),
+			#[cfg(feature = "gpu")]
+			Error::ComputePipelineCreation(err) => Some(err),
+			#[cfg(feature = "gpu")]
+			Error::DescriptorSetCreation(err) => Some(err),
+			#[cfg(feature = "gpu")]
+			Error::ImageCreation(err) => Some(err),
+			#[cfg(feature = "gpu")]
+			Error::ImageViewCreation(err) => Some(err),
+			#[cfg(feature = "gpu")]
+			Error::BufferCreation(err) => Some(err),
+			#[cfg(feature = "gpu")]
+			Error::OutOfMemory(err) => Some(err),
+			#[cfg(feature = "gpu")]
+			Error::Dispatch(err) => Some(err),
+			#[cfg(feature = "gpu")]
+			Error::CopyBufferImage(err) => Some(err),
+			#[cfg(feature = "gpu")]
+			Error::CommandBufferBuild(err) => Some(err),
+			#[cfg(feature = "gpu")]
+			Error::CommandBufferExec(err) => Some(err),
+			#[cfg(feature = "gpu")]
+			Error::Flush(err) => Some(err),
+			#[cfg(feature = "gpu")]
+			Error::BufferRead(err) => Some(err),
+			#[cfg(feature = "gpu")]
+			Error::NoComputeDevice | Error::NoQueue | Error::ShaderEntryPointNotFound(_) | Error::DescriptorSetLayoutNotFound | Error::CommandBufferNotBuilt => None,
+			Error::Cancelled => None
+		}
+	}
+}
+
+#[cfg(feature = "gpu")]
+impl From<InstanceCreationError> for Error {
+	fn from(err: InstanceCreationError) -> Self {
+		Error::InstanceCreation(err)
+	}
+}
+
+#[cfg(feature = "gpu")]
+impl From<DeviceCreationError> for Error {
+	fn from(err: DeviceCreationError) -> Self {
+		Error::DeviceCreation(err)
+	}
+}
+
+#[cfg(feature = "gpu")]
+impl From<ShaderCreationError> for Error {
+	fn from(err: ShaderCreationError) -> Self {
+		Error::ShaderCreation(err)
+	}
+}
+
+#[cfg(feature = "gpu")]
+impl From<ComputePipelineCreationError> for Error {
+	fn from(err: ComputePipelineCreationError) -> Self {
+		Error::ComputePipelineCreation(err)
+	}
+}
+
+#[cfg(feature = "gpu")]
+impl From<DescriptorSetCreationError> for Error {
+	fn from(err: DescriptorSetCreationError) -> Self {
+		Error::DescriptorSetCreation(err)
+	}
+}
+
+#[cfg(feature = "gpu")]
+impl From<ImageCreationError> for Error {
+	fn from(err: ImageCreationError) -> Self {
+		Error::ImageCreation(err)
+	}
+}
+
+#[cfg(feature = "gpu")]
+impl From<ImageViewCreationError> for Error {
+	fn from(err: ImageViewCreationError) -> Self {
+		Error::ImageViewCreation(err)
+	}
+}
+
+#[cfg(feature = "gpu")]
+impl From<DeviceMemoryAllocError> for Error {
+	fn from(err: DeviceMemoryAllocError) -> Self {
+		Error::BufferCreation(err)
+	}
+}
+
+#[cfg(feature = "gpu")]
+impl From<OomError> for Error {
+	fn from(err: OomError) -> Self {
+		Error::OutOfMemory(err)
+	}
+}
+
+#[cfg(feature = "gpu")]
+impl From<DispatchError> for Error {
+	fn from(err: DispatchError) -> Self {
+		Error::Dispatch(err)
+	}
+}
+
+#[cfg(feature = "gpu")]
+impl From<CopyBufferImageError> for Error {
+	fn from(err: CopyBufferImageError) -> Self {
+		Error::CopyBufferImage(err)
+	}
+}
+
+#[cfg(feature = "gpu")]
+impl From<BuildError> for Error {
+	fn from(err: BuildError) -> Self {
+		Error::CommandBufferBuild(err)
+	}
+}
+
+#[cfg(feature = "gpu")]
+impl From<CommandBufferExecError> for Error {
+	fn from(err: CommandBufferExecError) -> Self {
+		Error::CommandBufferExec(err)
+	}
+}
+
+#[cfg(feature = "gpu")]
+impl From<FlushError> for Error {
+	fn from(err: FlushError) -> Self {
+		Error::Flush(err)
+	}
+}
+
+#[cfg(feature = "gpu")]
+impl From<ReadLockError> for Error {
+	fn from(err: ReadLockError) -> Self {
+		Error::BufferRead(err)
+	}
+}
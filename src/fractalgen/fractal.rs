@@ -0,0 +1,172 @@
+//! This module defines the `Fractal` trait, which describes an escape-time fractal by how a single point in the complex plane is iterated, so that renderers can be generic over the fractal they render
+//!
+//! It also contains the generic functions `calculate_pixel` and `calculate_escape_data` that the renderers use to calculate the colour or escape data of a pixel for any `Fractal`
+
+use super::FractalType;
+use super::common::{PlaneTransform, Scalar};
+use super::mandelbrot::{Mandelbrot, InteriorChecks};
+use super::julia::Julia;
+use super::variants::{VariantMandelbrot, VariantJulia};
+use super::multibrot::{Multibrot, Multijulia};
+use super::colouring::ColourMap;
+use color_space::Rgb;
+use num::Complex;
+
+/// The max iterations used when none is specified
+pub const DEFAULT_MAX_ITERATIONS: u32 = 100;
+
+/// The result of iterating a single point of an escape-time fractal
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub struct EscapeData {
+	/// The number of iterations performed before the point escaped, or the max iterations if it did not escape
+	pub iterations: u32,
+	/// Whether the point escaped before the max iterations was reached
+	pub escaped: bool,
+	/// The value of z after the last iteration
+	pub final_z: Complex<f64>,
+	/// The derivative of the final z with respect to the point, if it was tracked
+	pub derivative: Option<Complex<f64>>,
+	/// The check that found the point to be inside the set without iterating it to the max iterations, if any
+	pub short_circuit: Option<ShortCircuit>
+}
+
+/// The ways a point can be found to be inside the set without iterating it to the max iterations, see `mandelbrot::InteriorChecks`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ShortCircuit {
+	/// The point is inside the main cardioid of the mandelbrot set
+	Cardioid,
+	/// The point is inside the period-2 bulb of the mandelbrot set
+	Bulb,
+	/// The orbit of the point became periodic
+	Periodic
+}
+
+/// An escape-time fractal, defined by how a point in the complex plane is iterated
+///
+/// Implement this to render your own escape-time formulas with the CPU renderers. `T` is the number type the point is calculated with, so implementing this for a `Scalar` other than f64 (e.g. `DoubleDouble`) allows rendering deeper zooms with a `PlaneTransform<T>`
+pub trait Fractal<T = f64> {
+	/// Iterates the point `point` until it escapes or `max_iterations` is reached, and reports how it escaped
+	fn iterate(&self, point: Complex<T>, max_iterations: u32) -> EscapeData;
+
+	/// Same as `iterate`, but also tracks the derivative of z with respect to the point. By default the derivative is not tracked
+	fn iterate_with_derivative(&self, point: Complex<T>, max_iterations: u32) -> EscapeData {
+		self.iterate(point, max_iterations)
+	}
+
+	/// The radius beyond which a point is considered to have escaped. Used for smooth colouring
+	fn escape_radius(&self) -> f64 {
+		2.
+	}
+
+	/// The degree of the iterated polynomial (2 for z^2 + c). Used for smooth colouring
+	fn degree(&self) -> f64 {
+		2.
+	}
+
+	/// Returns a lower bound on the distance from `point` to the boundary of the set if `point` can be proven to be inside the set within `max_iterations`, otherwise None. Used by `cpu_renderer::subdivision` to fill regions inside the set. By default no point is proven to be inside
+	fn interior_distance(&self, _point: Complex<T>, _max_iterations: u32) -> Option<f64> {
+		None
+	}
+}
+
+/// Calculates the colour of the pixel at (x, y) for `fractal` by transforming it into a coordinate in the complex plane using `transform`, and mapping its escape data to a colour with `colour_map`
+pub fn calculate_pixel<T, F, C>(fractal: &F, colour_map: &C, x: u32, y: u32, transform: &PlaneTransform<T>, max_iterations: Option<u32>) -> Rgb where T: Scalar, F: Fractal<T> + ?Sized, C: ColourMap + ?Sized {
+	let max_iterations = max_iterations.unwrap_or(DEFAULT_MAX_ITERATIONS);
+
+	let data = calculate_escape_data(fractal, x, y, transform, max_iterations, false);
+
+	colour_map.colour(&data, max_iterations)
+}
+
+/// Calculates the escape data of the pixel at (x, y) for `fractal` by transforming it into a coordinate in the complex plane using `transform`, also tracking the derivative if `track_derivative` is true
+pub fn calculate_escape_data<T, F>(fractal: &F, x: u32, y: u32, transform: &PlaneTransform<T>, max_iterations: u32, track_derivative: bool) -> EscapeData where T: Scalar, F: Fractal<T> + ?Sized {
+	let (cx, cy) = transform.transform((T::from_f64(x as f64), T::from_f64(y as f64)));
+
+	if track_derivative {
+		fractal.iterate_with_derivative(Complex::new(cx, cy), max_iterations)
+	} else {
+		fractal.iterate(Complex::new(cx, cy), max_iterations)
+	}
+}
+
+/// A `FractalType` iterated with `InteriorChecks`, which only affect `FractalType::MandelbrotSet`. The CPU renderers render a `RenderJob` with this
+#[derive(Clone, Copy)]
+pub struct CheckedFractalType {
+	pub fractal: FractalType,
+	pub interior_checks: InteriorChecks
+}
+
+impl<T> Fractal<T> for FractalType where T: Scalar {
+	fn iterate(&self, point: Complex<T>, max_iterations: u32) -> EscapeData {
+		match self {
+			FractalType::MandelbrotSet => Mandelbrot::default().iterate(point, max_iterations),
+			FractalType::JuliaSet { c } => Julia::new(*c).iterate(point, max_iterations),
+			FractalType::MandelbrotVariant(variant) => VariantMandelbrot::new(*variant).iterate(point, max_iterations),
+			FractalType::JuliaVariant { variant, c } => VariantJulia::new(*variant, *c).iterate(point, max_iterations),
+			FractalType::Multibrot { exponent } => Multibrot::new(*exponent).iterate(point, max_iterations),
+			FractalType::Multijulia { exponent, c } => Multijulia::new(*exponent, *c).iterate(point, max_iterations)
+		}
+	}
+
+	fn iterate_with_derivative(&self, point: Complex<T>, max_iterations: u32) -> EscapeData {
+		match self {
+			FractalType::MandelbrotSet => Mandelbrot::default().iterate_with_derivative(point, max_iterations),
+			FractalType::JuliaSet { c } => Julia::new(*c).iterate_with_derivative(point, max_iterations),
+			FractalType::MandelbrotVariant(variant) => VariantMandelbrot::new(*variant).iterate_with_derivative(point, max_iterations),
+			FractalType::JuliaVariant { variant, c } => VariantJulia::new(*variant, *c).iterate_with_derivative(point, max_iterations),
+			FractalType::Multibrot { exponent } => Multibrot::new(*exponent).iterate_with_derivative(point, max_iterations),
+			FractalType::Multijulia { exponent, c } => Multijulia::new(*exponent, *c).iterate_with_derivative(point, max_iterations)
+		}
+	}
+
+	fn escape_radius(&self) -> f64 {
+		match self {
+			FractalType::Multibrot { exponent } => Fractal::<T>::escape_radius(&Multibrot::new(*exponent)),
+			FractalType::Multijulia { exponent, c } => Fractal::<T>::escape_radius(&Multijulia::new(*exponent, *c)),
+			_ => 2.
+		}
+	}
+
+	fn degree(&self) -> f64 {
+		match self {
+			FractalType::Multibrot { exponent } => Fractal::<T>::degree(&Multibrot::new(*exponent)),
+			FractalType::Multijulia { exponent, c } => Fractal::<T>::degree(&Multijulia::new(*exponent, *c)),
+			_ => 2.
+		}
+	}
+
+	fn interior_distance(&self, point: Complex<T>, max_iterations: u32) -> Option<f64> {
+		match self {
+			FractalType::MandelbrotSet => Mandelbrot::default().interior_distance(point, max_iterations),
+			_ => None
+		}
+	}
+}
+
+impl<T> Fractal<T> for CheckedFractalType where T: Scalar {
+	fn iterate(&self, point: Complex<T>, max_iterations: u32) -> EscapeData {
+		match self.fractal {
+			FractalType::MandelbrotSet => Mandelbrot::default().interior_checks(self.interior_checks).iterate(point, max_iterations),
+			fractal => fractal.iterate(point, max_iterations)
+		}
+	}
+
+	fn iterate_with_derivative(&self, point: Complex<T>, max_iterations: u32) -> EscapeData {
+		match self.fractal {
+			FractalType::MandelbrotSet => Mandelbrot::default().interior_checks(self.interior_checks).iterate_with_derivative(point, max_iterations),
+			fractal => fractal.iterate_with_derivative(point, max_iterations)
+		}
+	}
+
+	fn escape_radius(&self) -> f64 {
+		Fractal::<T>::escape_radius(&self.fractal)
+	}
+
+	fn degree(&self) -> f64 {
+		Fractal::<T>::degree(&self.fractal)
+	}
+
+	fn interior_distance(&self, point: Complex<T>, max_iterations: u32) -> Option<f64> {
+		self.fractal.interior_distance(point, max_iterations)
+	}
+}
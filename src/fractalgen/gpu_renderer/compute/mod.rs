@@ -3,6 +3,12 @@ pub mod vulkan_compute;
 
 use crate::fractalgen::FractalType;
 use crate::fractalgen::PlaneTransform;
+use crate::fractalgen::ImageBufferFormat;
+use crate::fractalgen::Error;
+use crate::fractalgen::progress::{RenderOptions, ProgressTracker};
+use crate::fractalgen::{common, fractal, Fractal};
+use crate::fractalgen::colouring::ColourMapType;
+use crate::fractalgen::renderer::{Renderer, RenderJob};
 use crate::fractalgen::gpu_renderer::compute::vulkan_compute::VkComputeOperation;
 use crate::fractalgen::gpu_renderer::compute::vulkan_compute::VkDataStorage;
 use crate::fractalgen::gpu_renderer::compute::vulkan_compute::VkExtent;
@@ -32,7 +38,7 @@ fn test_vulkan_compute() {
 	use crate::fractalgen::gpu_renderer::compute::vulkan_compute::{VkComputeOperation, VkDataStorage, VkExtent, VkInstance};
 	use vulkano::buffer::{BufferUsage, CpuAccessibleBuffer};
 
-	let vk_instance = VkInstance::new();
+	let vk_instance = VkInstance::new().unwrap();
 
 	vk_instance.with_target(|vk_target| {
 		let data = vec![
@@ -47,8 +53,8 @@ fn test_vulkan_compute() {
 		];
 		let data_bindings = vec![vec![0]];
 		let shader = test_shader::load(vk_target.device.clone()).expect("Failed to create shader");
-		let mut op = VkComputeOperation::new(&vk_target, &data, &data_bindings, (shader, "main"), VkExtent::new(16000, 1, 1));
-		op.dispatch().build().execute().unwrap();
+		let mut op = VkComputeOperation::new(&vk_target, &data, &data_bindings, (shader, "main"), VkExtent::new(16000, 1, 1))?;
+		op.dispatch()?.build()?.execute()?;
 
 		// Check
 		if let VkDataStorage::BufferU32(buffer) = &data[0] {
@@ -58,41 +64,208 @@ fn test_vulkan_compute() {
 			}
 			println!("Test was successful!")
 		}
-	});
+
+		Ok(())
+	}).unwrap();
+}
+
+/// Generates an RGBA image of the specified fractal coloured with `colour_map` on the GPU, with given dimensions, and a defined transformation from the image coordinate plane to the complex plane, and the max_iterations is the amount of detail (50-100 being low, >=1000 being high, default 100)
+///
+/// If `smooth_colouring` is true, pixels are coloured by their normalised (fractional) iteration count instead of the number of iterations
+///
+/// Fails if no Vulkan device is available or if any step of the render fails on the GPU
+pub fn generate_fractal_image(fractal_type: FractalType, colour_map: ColourMapType, dimensions: (u32, u32), transform: &PlaneTransform<f64>, max_iterations: Option<u32>, smooth_colouring: bool) -> Result<RgbaImage, Error> {
+	let (width, height) = dimensions;
+
+	let img_buffer = render_rgba8(&ProgressTracker::new(&RenderOptions::new(), 0), fractal_type, colour_map, dimensions, transform, max_iterations, smooth_colouring)?;
+
+	Ok(RgbaImage::from_raw(width, height, img_buffer).unwrap())
+}
+
+/// Generates an image of the specified fractal coloured with `colour_map` on the GPU, with given dimensions, and a defined transformation from the image coordinate plane to the complex plane, and the max_iterations is the amount of detail (50-100 being low, >=1000 being high, default 100)
+///
+/// The image is written to the buffer contained within img_buffer_fmt, in the format specified
+///
+/// If `smooth_colouring` is true, pixels are coloured by their normalised (fractional) iteration count instead of the number of iterations
+///
+/// Fails if no Vulkan device is available or if any step of the render fails on the GPU, in which case the buffer is left untouched
+pub fn render_fractal_to(img_buffer_fmt: ImageBufferFormat, fractal_type: FractalType, colour_map: ColourMapType, dimensions: (u32, u32), transform: &PlaneTransform<f64>, max_iterations: Option<u32>, smooth_colouring: bool) -> Result<(), Error> {
+	render_fractal_to_with_options(&RenderOptions::new(), img_buffer_fmt, fractal_type, colour_map, dimensions, transform, max_iterations, smooth_colouring)
+}
+
+/// Same as `render_fractal_to`, but checks the cancellation token of `options` before each dispatch of `DISPATCH_ROWS` rows, returning `Error::Cancelled` if it is cancelled, and reports the number of dispatches completed to its progress callback
+///
+/// The buffer is left untouched if the render is cancelled
+#[allow(clippy::too_many_arguments)]
+pub fn render_fractal_to_with_options(options: &RenderOptions, img_buffer_fmt: ImageBufferFormat, fractal_type: FractalType, colour_map: ColourMapType, dimensions: (u32, u32), transform: &PlaneTransform<f64>, max_iterations: Option<u32>, smooth_colouring: bool) -> Result<(), Error> {
+	let tracker = ProgressTracker::new(options, dimensions.1.div_ceil(DISPATCH_ROWS) as u64);
+
+	let img_buffer = render_rgba8(&tracker, fractal_type, colour_map, dimensions, transform, max_iterations, smooth_colouring)?;
+
+	common::write_rgba8_to(img_buffer_fmt, &img_buffer);
+
+	Ok(())
+}
+
+/// Renders on the GPU with the compute backend
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ComputeRenderer;
+
+impl Renderer for ComputeRenderer {
+	fn render_fractal_to(&self, job: &RenderJob, img_buffer_fmt: ImageBufferFormat) -> Result<(), Error> {
+		render_fractal_to(img_buffer_fmt, job.fractal, job.colour_map, job.dimensions, &job.transform, job.max_iterations, job.smooth_colouring)
+	}
+
+	fn render_fractal_to_with_options(&self, job: &RenderJob, img_buffer_fmt: ImageBufferFormat, options: &RenderOptions) -> Result<(), Error> {
+		render_fractal_to_with_options(options, img_buffer_fmt, job.fractal, job.colour_map, job.dimensions, &job.transform, job.max_iterations, job.smooth_colouring)
+	}
 }
 
-// TODO: Use fractal_type and pass transform and max_iterations to shader in a buffer (need new type of buffer in VkDataStorage. *Sigh* ideally you should be able to use any type... Need to think of how to do this)
-pub fn generate_fractal_image<'a>(fractal_type: FractalType, dimensions: (u32, u32), transform: &PlaneTransform<f64>, max_iterations: Option<u32>) -> RgbaImage {
+/// The size of the work groups declared in the fractal shaders (local_size_x, local_size_y)
+const WORK_GROUP_SIZE: (u32, u32) = (8, 8);
+
+/// The most rows of the image rendered by one dispatch. Renders are split into dispatches of this many rows so that they can be cancelled between them (a multiple of the work group height)
+pub const DISPATCH_ROWS: u32 = 256;
+
+/// Sets the push constants of a fractal shader for the band with the given base offset
+type PushBandConstants = Box<dyn Fn(&mut VkComputeOperation, [f32; 2])>;
+
+/// Renders the fractal on the GPU in dispatches of `DISPATCH_ROWS` rows, checking for cancellation before and reporting progress after each one, returning the RGBA pixels in row order
+fn render_rgba8(tracker: &ProgressTracker, fractal_type: FractalType, colour_map: ColourMapType, dimensions: (u32, u32), transform: &PlaneTransform<f64>, max_iterations: Option<u32>, smooth_colouring: bool) -> Result<Vec<u8>, Error> {
 	let (width, height) = dimensions;
+	let max_iterations = max_iterations.unwrap_or(fractal::DEFAULT_MAX_ITERATIONS);
+	let band_height = height.min(DISPATCH_ROWS);
+
+	// The shaders calculate with single precision
+	let scale = [transform.scale_x as f32, transform.scale_y as f32];
+	let shear = [transform.shear_x as f32, transform.shear_y as f32];
+	let transformed_offset = [transform.transformed_offset_x as f32, transform.transformed_offset_y as f32];
+
+	tracker.check()?;
 
-	let vk_instance = VkInstance::new();
+	let vk_instance = VkInstance::new()?;
 
 	vk_instance.with_target(|vk_target| {
+		// Each dispatch renders one band of rows to an image of the height of a band, which is then copied to the pixels
 		let data = vec![
 			VkDataStorage::Image(
-				StorageImage::new(vk_target.device.clone(), ImageDimensions::Dim2d { width, height, array_layers: 1 },
-					Format::R8G8B8A8_UNORM, Some(vk_target.queue_family)).expect("Failed to create image")
+				StorageImage::new(vk_target.device.clone(), ImageDimensions::Dim2d { width, height: band_height, array_layers: 1 },
+					Format::R8G8B8A8_UNORM, Some(vk_target.queue_family))?
 			),
 			VkDataStorage::BufferU8(
 				CpuAccessibleBuffer::from_iter(vk_target.device.clone(), BufferUsage::all(), false,
-					(0..(width * height * 4)).map(|_| 0)).expect("Failed to create buffer")
+					(0..(width * band_height * 4)).map(|_| 0))?
 			)
 		];
 		let data_bindings = vec![vec![0]];
-		let shader = shaders::mandelbrot::load(vk_target.device.clone()).expect("Failed to create shader");
-		let mut op = VkComputeOperation::new(&vk_target, &data, &data_bindings, (shader, "main"), VkExtent::new(width, height, 1));
-		op.dispatch().add_commands(vec![
-			|builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>| {
-				let image = data[0].image().unwrap();
-				let buffer = data[1].buffer_u8().unwrap();
-				builder.copy_image_to_buffer(image.clone(), buffer.clone())
-					.expect("Failed to submit copy command to builder");
+		let mut pixels: Vec<u8> = Vec::with_capacity(width as usize * height as usize * 4);
+
+		// The pipeline is created once, and each band only changes the base offset in the push constants
+		let (shader, push_constants): (_, PushBandConstants) = match fractal_type {
+			FractalType::MandelbrotSet => {
+				let shader = shaders::mandelbrot::load(vk_target.device.clone())?;
+				(shader, Box::new(move |op, base_offset| {
+					op.push_constants(shaders::mandelbrot::ty::PushConstants {
+						scale,
+						shear,
+						base_offset,
+						transformed_offset,
+						max_iterations,
+						colour_map: colour_map as u32,
+						smooth_colouring: smooth_colouring as u32
+					});
+				}))
+			},
+			FractalType::JuliaSet { c } => {
+				let shader = shaders::julia::load(vk_target.device.clone())?;
+				(shader, Box::new(move |op, base_offset| {
+					op.push_constants(shaders::julia::ty::PushConstants {
+						scale,
+						shear,
+						base_offset,
+						transformed_offset,
+						c: [c.re as f32, c.im as f32],
+						max_iterations,
+						colour_map: colour_map as u32,
+						smooth_colouring: smooth_colouring as u32
+					});
+				}))
+			},
+			FractalType::MandelbrotVariant(variant) | FractalType::JuliaVariant { variant, .. } => {
+				let c = match fractal_type {
+					FractalType::JuliaVariant { c, .. } => Some(c),
+					_ => None
+				};
+				let shader = shaders::variants::load(vk_target.device.clone())?;
+				(shader, Box::new(move |op, base_offset| {
+					op.push_constants(shaders::variants::ty::PushConstants {
+						scale,
+						shear,
+						base_offset,
+						transformed_offset,
+						c: c.map_or([0., 0.], |c| [c.re as f32, c.im as f32]),
+						variant: variant as u32,
+						julia: c.is_some() as u32,
+						max_iterations,
+						colour_map: colour_map as u32,
+						smooth_colouring: smooth_colouring as u32
+					});
+				}))
+			},
+			FractalType::Multibrot { exponent } | FractalType::Multijulia { exponent, .. } => {
+				let c = match fractal_type {
+					FractalType::Multijulia { c, .. } => Some(c),
+					_ => None
+				};
+				let shader = shaders::multibrot::load(vk_target.device.clone())?;
+				(shader, Box::new(move |op, base_offset| {
+					op.push_constants(shaders::multibrot::ty::PushConstants {
+						scale,
+						shear,
+						base_offset,
+						transformed_offset,
+						c: c.map_or([0., 0.], |c| [c.re as f32, c.im as f32]),
+						exponent: exponent as f32,
+						escape_radius: Fractal::<f64>::escape_radius(&fractal_type) as f32,
+						julia: c.is_some() as u32,
+						max_iterations,
+						colour_map: colour_map as u32,
+						smooth_colouring: smooth_colouring as u32
+					});
+				}))
 			}
-		]).build().execute().unwrap();
+		};
+
+		let mut op = VkComputeOperation::new(&vk_target, &data, &data_bindings, (shader, "main"), VkExtent::new(0, 0, 0))?;
+
+		for band_start in (0..height).step_by(band_height.max(1) as usize) {
+			tracker.check()?;
+
+			let rows = band_height.min(height - band_start);
 
-		let working_buffer_content = data[1].buffer_u8().unwrap().read().unwrap()[..].to_vec();
+			// Offsetting the base plane by the start of the band makes the first row of the band image map to the row band_start of the full image
+			let base_offset = [transform.base_offset_x as f32, (transform.base_offset_y - band_start as f64) as f32];
+
+			// Enough work groups to cover every pixel of the band
+			op.reset()?;
+			op.extent = VkExtent::new(width.div_ceil(WORK_GROUP_SIZE.0), rows.div_ceil(WORK_GROUP_SIZE.1), 1);
+			push_constants(&mut op, base_offset);
+			op.dispatch()?.add_commands(vec![
+				|builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>| {
+					let image = data[0].image().unwrap();
+					let buffer = data[1].buffer_u8().unwrap();
+					builder.copy_image_to_buffer(image.clone(), buffer.clone())?;
+					Ok(())
+				}
+			])?.build()?.execute()?;
+
+			// The last band may be shorter than the image, so only its rows are copied
+			pixels.extend_from_slice(&data[1].buffer_u8().unwrap().read()?[..(width * rows * 4) as usize]);
+
+			tracker.advance(1);
+		}
 
-		RgbaImage::from_raw(width, height, working_buffer_content).unwrap()
+		Ok(pixels)
 	})
 
 	// todo!();
@@ -3,4 +3,25 @@ pub mod mandelbrot {
 		ty: "compute",
 		path: "shaders/compute_mandelbrot.comp"
 	}
+}
+
+pub mod julia {
+	vulkano_shaders::shader!{
+		ty: "compute",
+		path: "shaders/compute_julia.comp"
+	}
+}
+
+pub mod variants {
+	vulkano_shaders::shader!{
+		ty: "compute",
+		path: "shaders/compute_variants.comp"
+	}
+}
+
+pub mod multibrot {
+	vulkano_shaders::shader!{
+		ty: "compute",
+		path: "shaders/compute_multibrot.comp"
+	}
 }
\ No newline at end of file
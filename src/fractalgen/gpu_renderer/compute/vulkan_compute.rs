@@ -13,6 +13,8 @@ use vulkano::pipeline::{Pipeline, ComputePipeline, PipelineBindPoint};
 use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
 use vulkano::shader::ShaderModule;
 
+use crate::fractalgen::Error;
+
 use std::sync::Arc;
 
 #[derive(Clone)]
@@ -29,8 +31,10 @@ pub struct VkTarget<'a> {
 
 pub struct VkComputeOperation<'a> {
 	pub vk_target: &'a VkTarget<'a>,
+	pub pipeline: Arc<ComputePipeline>,
 	pub command_buffer_builder: Option<AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>>,
 	pub command_buffer: Option<Arc<PrimaryAutoCommandBuffer>>,
+	pub descriptor_sets: Vec<Arc<PersistentDescriptorSet>>,
 	pub data: &'a Vec<VkDataStorage>,
 	pub data_bindings: &'a Vec<Vec<u32>>,
 	pub extent: VkExtent
@@ -51,52 +55,53 @@ pub struct VkExtent {
 }
 
 impl VkInstance {
-	pub fn new() -> Self {
+	/// Fails if no Vulkan implementation is available
+	pub fn new() -> Result<Self, Error> {
 		let extensions = InstanceExtensions {
 			.. InstanceExtensions::none()
 		};
 
-		VkInstance {
-			instance: Instance::new(None, Version::V1_1, &extensions, None).expect("Failed to create Vulkan instance")
-		}
+		Ok(VkInstance {
+			instance: Instance::new(None, Version::V1_1, &extensions, None)?
+		})
 	}
 
 	/// Use the scoped thread pool method of taking a closure and executing it within a scope where vk_instance is always valid
-	pub fn with_target<F, R>(&self, vk_target_scope: F) -> R where F: FnOnce(VkTarget) -> R {
-		vk_target_scope(VkTarget::new(self))
+	///
+	/// Fails without calling `vk_target_scope` if no target could be created, otherwise returns the result of `vk_target_scope`
+	pub fn with_target<F, R>(&self, vk_target_scope: F) -> Result<R, Error> where F: FnOnce(VkTarget) -> Result<R, Error> {
+		vk_target_scope(VkTarget::new(self)?)
 	}
 }
 
 impl<'a> VkTarget<'a> {
 	/// Attempts to find the best Vulkan implementation available and the best QueueFamilies/Queues
-	pub fn new(vk_instance: &'a VkInstance) -> Self {
+	pub fn new(vk_instance: &'a VkInstance) -> Result<Self, Error> {
 		let device_extensions = DeviceExtensions {
 			..DeviceExtensions::none()
 		};
 
-		let (physical, queue_family) = VkTarget::select_compute_device(&vk_instance.instance, &device_extensions);
-
-		println!("Using vulkan device: {} (type: {:?})", physical.properties().device_name, physical.properties().device_type);
+		let (physical, queue_family) = VkTarget::select_compute_device(&vk_instance.instance, &device_extensions)?;
 
 		let (device, mut queues) = Device::new(
 			physical,
 			&Features::none(),
 			&physical.required_extensions().union(&device_extensions),
 			[(queue_family, 0.5)].iter().cloned()
-		).expect("Failed to create a device");
+		)?;
 
-		let queue = queues.next().unwrap();
+		let queue = queues.next().ok_or(Error::NoQueue)?;
 
-		VkTarget {
+		Ok(VkTarget {
 			physical,
 			queue_family,
 			device,
 			queue
-		}
+		})
 	}
 
 	// Attempts to find the best Vulkan implementation and QueueFamily
-	pub fn select_compute_device(instance: &'a Arc<Instance>, device_extensions: &DeviceExtensions) -> (PhysicalDevice<'a>, QueueFamily<'a>) {
+	pub fn select_compute_device(instance: &'a Arc<Instance>, device_extensions: &DeviceExtensions) -> Result<(PhysicalDevice<'a>, QueueFamily<'a>), Error> {
 		PhysicalDevice::enumerate(&instance)
 			.filter(|&p| p.supported_extensions().is_superset_of(&device_extensions))
 			.filter_map(|p| {
@@ -111,99 +116,120 @@ impl<'a> VkTarget<'a> {
 				PhysicalDeviceType::VirtualGpu => 2,
 				PhysicalDeviceType::Cpu => 3,
 				PhysicalDeviceType::Other => 4
-			}).expect("No vulkan implementations found")
+			}).ok_or(Error::NoComputeDevice)
 	}
 }
 
 impl<'a> VkComputeOperation<'a> {
 	/// If any dimension of `extent` is 0, the shader will not run
-	pub fn new(vk_target: &'a VkTarget<'a>, data: &'a Vec<VkDataStorage>, data_bindings: &'a Vec<Vec<u32>>, shader_entry_point: (Arc<ShaderModule>, &str), extent: VkExtent) -> Self {
+	pub fn new(vk_target: &'a VkTarget<'a>, data: &'a Vec<VkDataStorage>, data_bindings: &'a Vec<Vec<u32>>, shader_entry_point: (Arc<ShaderModule>, &str), extent: VkExtent) -> Result<Self, Error> {
+		let entry_point = shader_entry_point.0.entry_point(shader_entry_point.1).ok_or_else(|| Error::ShaderEntryPointNotFound(shader_entry_point.1.to_string()))?;
+
 		let pipeline = ComputePipeline::new(
 			vk_target.device.clone(),
-			shader_entry_point.0.entry_point(shader_entry_point.1).expect(&format!("Entry point \"{}\" not found or multiple instances found", shader_entry_point.1)),
+			entry_point,
 			&(),
 			None,
 			|_| {}
-		).expect("Failed to create compute pipeline");
+		)?;
 
-		let layout = pipeline.layout().descriptor_set_layouts().get(0).unwrap();
+		let layout = pipeline.layout().descriptor_set_layouts().get(0).ok_or(Error::DescriptorSetLayoutNotFound)?;
 
-		let descriptor_sets: Vec<Arc<PersistentDescriptorSet>> = data_bindings.iter().map(|set_bindings| {
-			PersistentDescriptorSet::new(layout.clone(), set_bindings.iter().enumerate().map(|(binding, i)| {
-				match &data[*i as usize] {
-					VkDataStorage::Image(image) => WriteDescriptorSet::image_view(binding as u32, ImageView::new(image.clone()).expect("Could not create ImageView")),
+		let descriptor_sets = data_bindings.iter().map(|set_bindings| {
+			let writes = set_bindings.iter().enumerate().map(|(binding, i)| {
+				Ok(match &data[*i as usize] {
+					VkDataStorage::Image(image) => WriteDescriptorSet::image_view(binding as u32, ImageView::new(image.clone())?),
 					VkDataStorage::BufferU8(buffer) => WriteDescriptorSet::buffer(binding as u32, buffer.clone()),
 					VkDataStorage::BufferU32(buffer) => WriteDescriptorSet::buffer(binding as u32, buffer.clone()),
-				}
-			})).expect("Failed to create descriptor set")
-		}).collect();
-
-		let mut builder = AutoCommandBufferBuilder::primary(
-			vk_target.device.clone(),
-			vk_target.queue_family.clone(),
-			CommandBufferUsage::OneTimeSubmit
-		).expect("Failed to create AutoCommandBufferBuilder");
+				})
+			}).collect::<Result<Vec<WriteDescriptorSet>, Error>>()?;
 
-		builder
-			.bind_pipeline_compute(pipeline.clone())
-			.bind_descriptor_sets(PipelineBindPoint::Compute, pipeline.layout().clone(), 0, descriptor_sets);
+			Ok(PersistentDescriptorSet::new(layout.clone(), writes)?)
+		}).collect::<Result<Vec<Arc<PersistentDescriptorSet>>, Error>>()?;
 
-		VkComputeOperation {
+		let mut op = VkComputeOperation {
 			vk_target,
-			command_buffer_builder: Some(builder),
+			pipeline,
+			command_buffer_builder: None,
 			command_buffer: None,
+			descriptor_sets,
 			data,
 			data_bindings,
 			extent
-		}
+		};
+		op.reset()?;
+
+		Ok(op)
 	}
 
-	/// Allows adding VkCommands before the dispatch call
-	pub fn add_commands<VkCommand>(&mut self, vk_commands: Vec<VkCommand>) -> &mut Self where VkCommand: FnOnce(&mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>) -> () {
+	/// Discards the command buffer and starts a new one with the pipeline and data bound, so that the operation can be dispatched again (e.g. with different push constants or extent) without recreating the pipeline
+	pub fn reset(&mut self) -> Result<&mut Self, Error> {
+		let mut builder = AutoCommandBufferBuilder::primary(
+			self.vk_target.device.clone(),
+			self.vk_target.queue_family.clone(),
+			CommandBufferUsage::OneTimeSubmit
+		)?;
+
+		builder
+			.bind_pipeline_compute(self.pipeline.clone())
+			.bind_descriptor_sets(PipelineBindPoint::Compute, self.pipeline.layout().clone(), 0, self.descriptor_sets.clone());
+
+		self.command_buffer_builder = Some(builder);
+		self.command_buffer = None;
+
+		Ok(self)
+	}
+
+	/// Allows adding VkCommands before or after the dispatch call. Stops at and returns the first error returned by a VkCommand
+	pub fn add_commands<VkCommand>(&mut self, vk_commands: Vec<VkCommand>) -> Result<&mut Self, Error> where VkCommand: FnOnce(&mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>) -> Result<(), Error> {
 		if let Some(builder) = &mut self.command_buffer_builder {
 			// Execute supplied VkCommands
 			for command in vk_commands {
-				command(builder);
+				command(builder)?;
 			}
 		};
+		Ok(self)
+	}
+
+	/// Sets the push constants of the shader to `push_constants`. Must be called before the dispatch call
+	pub fn push_constants<Pc>(&mut self, push_constants: Pc) -> &mut Self {
+		if let Some(builder) = &mut self.command_buffer_builder {
+			builder.push_constants(self.pipeline.layout().clone(), 0, push_constants);
+		}
 		self
 	}
 
 	/// Adds the dispatch call to the command buffer builder
-	pub fn dispatch(&mut self) -> &mut Self {
+	pub fn dispatch(&mut self) -> Result<&mut Self, Error> {
 		if let Some(builder) = &mut self.command_buffer_builder {
-			builder
-				.dispatch([self.extent.size_x, self.extent.size_y, self.extent.size_z])
-				.expect("Failed to add the dispatch command to the AutoCommandBufferBuilder");
+			builder.dispatch([self.extent.size_x, self.extent.size_y, self.extent.size_z])?;
 		}
-		self
+		Ok(self)
 	}
 
 	/// Builds the command buffer
-	pub fn build(&mut self) -> &mut Self {
+	pub fn build(&mut self) -> Result<&mut Self, Error> {
 		if let Some(builder) = self.command_buffer_builder.take() {
 			self.command_buffer = Some(
-				Arc::new(builder.build().expect("Failed to build command buffer"))
+				Arc::new(builder.build()?)
 			);
 		};
-		self
+		Ok(self)
 	}
 
-	/// Submits the command buffer to the GPU to be executed
-	pub fn execute(&self) -> Result<(), &str> {
+	/// Submits the command buffer to the GPU to be executed, waiting for it to complete
+	pub fn execute(&self) -> Result<(), Error> {
 		match &self.command_buffer {
 			Some(command_buffer) => {
 				let future = sync::now(self.vk_target.device.clone())
-					.then_execute(self.vk_target.queue.clone(), command_buffer.clone())
-					.expect("Failed to send command buffer to GPU for execution")
-					.then_signal_fence_and_flush()
-					.expect("Failed to instruct GPU to signal fence upon completion and/or flush");
+					.then_execute(self.vk_target.queue.clone(), command_buffer.clone())?
+					.then_signal_fence_and_flush()?;
 
-				future.wait(None).expect("Timed out");
+				future.wait(None)?;
 
 				Ok(())
 			},
-			None => Err("Command buffer was not built")
+			None => Err(Error::CommandBufferNotBuilt)
 		}
 	}
 }
@@ -5,4 +5,5 @@
 /// This module uses compute shaders to calculate the fractal image
 pub mod compute;
 /// This module uses graphics shaders to render the fractal image to a context
+#[cfg(feature = "window")]
 pub mod render;
@@ -0,0 +1,56 @@
+//! This module contains the `IterationBuffer` struct, which holds the raw escape data of every pixel of a render
+//!
+//! Rendering to an `IterationBuffer` and then colouring it separately means the palette can be changed without rerunning the escape-time computation
+
+use super::fractal::{EscapeData, ShortCircuit};
+
+/// A per-pixel buffer of the escape data of a render, stored row by row
+#[derive(Clone)]
+pub struct IterationBuffer {
+	pub width: u32,
+	pub height: u32,
+	/// The max iterations the escape data was calculated with
+	pub max_iterations: u32,
+	pub data: Vec<EscapeData>
+}
+
+impl IterationBuffer {
+	/// Returns the escape data of the pixel at (x, y)
+	pub fn get(&self, x: u32, y: u32) -> &EscapeData {
+		&self.data[x as usize + y as usize * self.width as usize]
+	}
+
+	pub fn dimensions(&self) -> (u32, u32) {
+		(self.width, self.height)
+	}
+
+	/// Counts the pixels that were found to be inside the set by each interior check, rather than being iterated to the max iterations
+	pub fn short_circuits(&self) -> ShortCircuitCounts {
+		let mut counts = ShortCircuitCounts::default();
+
+		for data in &self.data {
+			match data.short_circuit {
+				Some(ShortCircuit::Cardioid) => counts.cardioid += 1,
+				Some(ShortCircuit::Bulb) => counts.bulb += 1,
+				Some(ShortCircuit::Periodic) => counts.periodic += 1,
+				None => ()
+			}
+		}
+
+		counts
+	}
+}
+
+/// The number of pixels of a render found to be inside the set by each interior check, see `mandelbrot::InteriorChecks`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub struct ShortCircuitCounts {
+	pub cardioid: u64,
+	pub bulb: u64,
+	pub periodic: u64
+}
+
+impl ShortCircuitCounts {
+	pub fn total(&self) -> u64 {
+		self.cardioid + self.bulb + self.periodic
+	}
+}
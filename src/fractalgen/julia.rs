@@ -1,24 +1,81 @@
-//! This module contains the function `calculate_pixel` to calculate the colour of the pixel at (x, y) for the julia set by transforming it into a coordinate in the complex plane using a defined transformation
+//! This module contains the `Julia` fractal, and the function `calculate_pixel` to calculate the colour of the pixel at (x, y) for the julia set of a constant c by transforming it into a coordinate in the complex plane using a defined transformation
 //!
 //! For colouring rules, it linearly maps the number of iterations taken for z to escape to hue in the HSV/HSB colour space, and if it doesn't escape then it returns black
 
-use super::common::PlaneTransform;
+use super::common::{PlaneTransform, Scalar};
+use super::fractal::{self, Fractal, EscapeData};
 use color_space::Rgb;
+use num::Complex;
 use super::colouring;
 
-pub fn calculate_pixel(x: u32, y: u32, transform: &PlaneTransform<f64>, max_iterations: Option<u32>) -> Rgb {
-	let max_iterations = max_iterations.unwrap_or(100);
+/// The constant c of the julia set used when none is given
+pub const DEFAULT_C: Complex<f64> = Complex::new(-0.4, 0.6);
 
-	let (cx, cy) = transform.transform((x as f64, y as f64));
+/// The julia set for the constant c, iterating z = z^2 + c starting from z = the point being iterated
+#[derive(Clone, Copy, Debug)]
+pub struct Julia {
+	pub c: Complex<f64>
+}
 
-	let c = num::Complex::new(-0.4, 0.6);
-	let mut z = num::Complex::new(cx, cy);
+impl Julia {
+	pub fn new(c: Complex<f64>) -> Self {
+		Julia { c }
+	}
+}
+
+impl Default for Julia {
+	/// The julia set for `DEFAULT_C`
+	fn default() -> Self {
+		Julia::new(DEFAULT_C)
+	}
+}
+
+impl<T> Fractal<T> for Julia where T: Scalar {
+	fn iterate(&self, point: Complex<T>, max_iterations: u32) -> EscapeData {
+		let c = Complex::new(T::from_f64(self.c.re), T::from_f64(self.c.im));
+		let mut z = point;
+		let four = T::from_f64(4.);
+
+		let mut i = 0;
+		while i < max_iterations && z.re * z.re + z.im * z.im <= four { // If z increases beyond 2, then it is not in the julia set
+			z = Complex::new(z.re * z.re - z.im * z.im + c.re, (z.re + z.re) * z.im + c.im);
+			i += 1;
+		}
+
+		EscapeData {
+			iterations: i,
+			escaped: i < max_iterations,
+			final_z: Complex::new(z.re.to_f64(), z.im.to_f64()),
+			derivative: None,
+			short_circuit: None
+		}
+	}
+
+	fn iterate_with_derivative(&self, point: Complex<T>, max_iterations: u32) -> EscapeData {
+		let c = Complex::new(T::from_f64(self.c.re), T::from_f64(self.c.im));
+		let mut z = point;
+		let mut dz = Complex::new(T::one(), T::zero());
+		let four = T::from_f64(4.);
+
+		let mut i = 0;
+		while i < max_iterations && z.re * z.re + z.im * z.im <= four {
+			// dz/dz0 = 2 * z * dz
+			let (re2, im2) = (z.re + z.re, z.im + z.im);
+			dz = Complex::new(re2 * dz.re - im2 * dz.im, re2 * dz.im + im2 * dz.re);
+			z = Complex::new(z.re * z.re - z.im * z.im + c.re, (z.re + z.re) * z.im + c.im);
+			i += 1;
+		}
 
-	let mut i = 0;
-	while i < max_iterations && z.norm() <= 2. { // If z increases beyond 2, then it is not in the julia set
-		z = z * z + c;
-		i += 1;
+		EscapeData {
+			iterations: i,
+			escaped: i < max_iterations,
+			final_z: Complex::new(z.re.to_f64(), z.im.to_f64()),
+			derivative: Some(Complex::new(dz.re.to_f64(), dz.im.to_f64())),
+			short_circuit: None
+		}
 	}
+}
 
-	colouring::map_to_colour(i as f64, max_iterations as f64)
-}
\ No newline at end of file
+pub fn calculate_pixel<T>(c: Complex<f64>, x: u32, y: u32, transform: &PlaneTransform<T>, max_iterations: Option<u32>) -> Rgb where T: Scalar {
+	fractal::calculate_pixel(&Julia::new(c), &colouring::map_to_colour, x, y, transform, max_iterations)
+}
@@ -1,24 +1,321 @@
-//! This module contains the function `calculate_pixel` to calculate the colour of the pixel at (x, y) for the mandelbrot set by transforming it into a coordinate in the complex plane using a defined transformation
+//! This module contains the `Mandelbrot` fractal, and the function `calculate_pixel` to calculate the colour of the pixel at (x, y) for the mandelbrot set by transforming it into a coordinate in the complex plane using a defined transformation
 //!
 //! For colouring rules, it linearly maps the number of iterations taken for z to escape to hue in the HSV/HSB colour space, and if it doesn't escape then it returns black
 
-use super::common::PlaneTransform;
+use super::common::{PlaneTransform, Scalar};
+use super::fractal::{self, Fractal, EscapeData, ShortCircuit};
 use color_space::Rgb;
+use num::Complex;
 use super::colouring;
 
-pub fn calculate_pixel(x: u32, y: u32, transform: &PlaneTransform<f64>, max_iterations: Option<u32>) -> Rgb {
-	let max_iterations = max_iterations.unwrap_or(100);
+/// The mandelbrot set, iterating z = z^2 + c starting from z = 0, where c is the point being iterated
+///
+/// Implements `Fractal<T>` for every `Scalar`, calculating with f64 by default. By default every point inside the set is iterated to the max iterations, but `InteriorChecks` can be enabled to detect most of them sooner
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Mandelbrot {
+	pub interior_checks: InteriorChecks
+}
 
-	let (cx, cy) = transform.transform((x as f64, y as f64));
+/// Checks that detect points inside the mandelbrot set without iterating them to the max iterations. Points detected by a check report the max iterations and the `ShortCircuit` that detected them
+///
+/// By default no checks are enabled
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct InteriorChecks {
+	/// Whether to test if the point is inside the main cardioid or the period-2 bulb before iterating it. This is exact
+	pub cardioid_bulb: bool,
+	/// Whether to stop iterating when the orbit returns to within `periodicity_tolerance` of an earlier value, detected with Brent's algorithm. Points outside the set whose orbits pass this close to a cycle are wrongly detected, so the tolerance should be well below the pixel spacing
+	pub periodicity: bool,
+	pub periodicity_tolerance: f64
+}
 
-	let c = num::Complex::new(cx, cy);
-	let mut z = num::Complex::new(0., 0.);
+impl Mandelbrot {
+	/// Builder-style method
+	pub fn interior_checks(mut self, interior_checks: InteriorChecks) -> Self {
+		self.interior_checks = interior_checks;
+		self
+	}
+
+	/// Iterates `point`, also tracking the derivative if `track_derivative` is true
+	fn iterate_checked<T>(&self, point: Complex<T>, max_iterations: u32, track_derivative: bool) -> EscapeData where T: Scalar {
+		let c = point;
+		let mut z = Complex::new(T::zero(), T::zero());
+		let mut dz = Complex::new(T::zero(), T::zero());
+		let four = T::from_f64(4.);
+
+		if self.interior_checks.cardioid_bulb && max_iterations > 0 {
+			if let Some(short_circuit) = check_cardioid_bulb(c) {
+				return EscapeData {
+					iterations: max_iterations,
+					escaped: false,
+					final_z: Complex::new(0., 0.),
+					derivative: None,
+					short_circuit: Some(short_circuit)
+				};
+			}
+		}
+
+		// Brent's algorithm: compare z to a saved value, which is moved forward to z after a number of iterations that doubles each time, so cycles of any period are eventually found
+		let tolerance_sqr = T::from_f64(self.interior_checks.periodicity_tolerance * self.interior_checks.periodicity_tolerance);
+		let mut saved = z;
+		let mut since_saved = 0;
+		let mut save_interval = 1;
+
+		let mut i = 0;
+		while i < max_iterations && z.re * z.re + z.im * z.im <= four { // If z increases beyond 2, then it is not in the mandelbrot set
+			if track_derivative {
+				// dz/dc = 2 * z * dz + 1
+				let (re2, im2) = (z.re + z.re, z.im + z.im);
+				dz = Complex::new(re2 * dz.re - im2 * dz.im + T::one(), re2 * dz.im + im2 * dz.re);
+			}
+			z = Complex::new(z.re * z.re - z.im * z.im + c.re, (z.re + z.re) * z.im + c.im);
+			i += 1;
+
+			if self.interior_checks.periodicity {
+				let (dr, di) = (z.re - saved.re, z.im - saved.im);
+				if dr * dr + di * di <= tolerance_sqr {
+					return EscapeData {
+						iterations: max_iterations,
+						escaped: false,
+						final_z: Complex::new(z.re.to_f64(), z.im.to_f64()),
+						derivative: track_derivative.then(|| Complex::new(dz.re.to_f64(), dz.im.to_f64())),
+						short_circuit: Some(ShortCircuit::Periodic)
+					};
+				}
+
+				since_saved += 1;
+				if since_saved == save_interval {
+					saved = z;
+					since_saved = 0;
+					save_interval *= 2;
+				}
+			}
+		}
+
+		EscapeData {
+			iterations: i,
+			escaped: i < max_iterations,
+			final_z: Complex::new(z.re.to_f64(), z.im.to_f64()),
+			derivative: track_derivative.then(|| Complex::new(dz.re.to_f64(), dz.im.to_f64())),
+			short_circuit: None
+		}
+	}
+}
+
+impl<T> Fractal<T> for Mandelbrot where T: Scalar {
+	fn iterate(&self, point: Complex<T>, max_iterations: u32) -> EscapeData {
+		self.iterate_checked(point, max_iterations, false)
+	}
+
+	fn iterate_with_derivative(&self, point: Complex<T>, max_iterations: u32) -> EscapeData {
+		self.iterate_checked(point, max_iterations, true)
+	}
+
+	fn interior_distance(&self, point: Complex<T>, max_iterations: u32) -> Option<f64> {
+		// The estimate is calculated in f64, so the bound is reduced by how far the point moved when rounded to f64
+		let c = Complex::new(point.re.to_f64(), point.im.to_f64());
+		let (dr, di) = (point.re - T::from_f64(c.re), point.im - T::from_f64(c.im));
+		let rounding = (dr * dr + di * di).to_f64().sqrt();
+
+		interior_distance(c, max_iterations).map(|distance| distance - rounding).filter(|&distance| distance > 0.)
+	}
+}
+
+impl Default for InteriorChecks {
+	fn default() -> Self {
+		InteriorChecks {
+			cardioid_bulb: false,
+			periodicity: false,
+			periodicity_tolerance: 1e-14
+		}
+	}
+}
+
+impl InteriorChecks {
+	/// Enables every check, with the default periodicity tolerance
+	pub fn all() -> Self {
+		InteriorChecks::default().cardioid_bulb(true).periodicity(true)
+	}
+
+	/// Builder-style method
+	pub fn cardioid_bulb(mut self, cardioid_bulb: bool) -> Self {
+		self.cardioid_bulb = cardioid_bulb;
+		self
+	}
+
+	/// Builder-style method
+	pub fn periodicity(mut self, periodicity: bool) -> Self {
+		self.periodicity = periodicity;
+		self
+	}
+
+	/// Builder-style method
+	pub fn periodicity_tolerance(mut self, periodicity_tolerance: f64) -> Self {
+		self.periodicity_tolerance = periodicity_tolerance;
+		self
+	}
+}
+
+/// Returns which of the main cardioid and the period-2 bulb c is inside, if either
+pub fn check_cardioid_bulb<T>(c: Complex<T>) -> Option<ShortCircuit> where T: Scalar {
+	let y2 = c.im * c.im;
+
+	// With q = (x - 1/4)^2 + y^2, c is inside the cardioid if q(q + x - 1/4) <= y^2 / 4
+	let x = c.re - T::from_f64(0.25);
+	let q = x * x + y2;
+	if q * (q + x) <= y2 / 4. {
+		return Some(ShortCircuit::Cardioid);
+	}
+
+	// The bulb is the disc of radius 1/4 around -1
+	let x = c.re + T::one();
+	if x * x + y2 <= T::from_f64(0.0625) {
+		return Some(ShortCircuit::Bulb);
+	}
+
+	None
+}
 
-	let mut i = 0;
-	while i < max_iterations && z.norm_sqr() <= 4. { // If z increases beyond 2, then it is not in the mandelbrot set
+/// Returns a lower bound on the distance from c to the boundary of the mandelbrot set if c is inside a hyperbolic component whose attracting cycle can be found within `max_iterations`, otherwise None
+///
+/// The period is guessed as the iteration at which |z| is smallest, a point of the cycle is found with Newton's method, and the interior distance estimate b is calculated from the derivatives of the cycle. The distance from c to the boundary of its component (and so of the set) is between b / 4 and b, so b / 4 is returned. See https://en.wikibooks.org/wiki/Fractals/Iterations_in_the_complex_plane/Mandelbrot_set_interior
+pub fn interior_distance(c: Complex<f64>, max_iterations: u32) -> Option<f64> {
+	let one = Complex::new(1., 0.);
+
+	let mut z = Complex::new(0., 0.);
+	let mut min_norm = f64::INFINITY;
+	let mut period = 0;
+	for i in 1..=max_iterations {
 		z = z * z + c;
-		i += 1;
+		let norm = z.norm_sqr();
+		if norm > 4. {
+			return None;
+		}
+		if norm < min_norm {
+			min_norm = norm;
+			period = i;
+		}
+	}
+
+	// Newton's method for z0 = f^period(z0), starting from the last z which is close to the cycle if the orbit has been attracted to it
+	let mut z0 = z;
+	for _ in 0..64 {
+		let (mut w, mut dw) = (z0, one);
+		for _ in 0..period {
+			dw = dw * w * 2.;
+			w = w * w + c;
+		}
+		let step = (w - z0) / (dw - one);
+		z0 -= step;
+
+		if !z0.is_finite() {
+			return None;
+		}
+		if step.norm() <= 1e-15 * (1. + z0.norm()) {
+			break;
+		}
+	}
+
+	// The derivatives of f^n(z0) with respect to z0 and c, until the orbit first returns to z0. The period found may be a multiple of the true period, which the estimate must use
+	let (mut z, mut dz, mut dc, mut dzdz, mut dcdz) = (z0, one, Complex::new(0., 0.), Complex::new(0., 0.), Complex::new(0., 0.));
+	for _ in 0..period {
+		dcdz = (z * dcdz + dc * dz) * 2.;
+		dzdz = (z * dzdz + dz * dz) * 2.;
+		dc = z * dc * 2. + one;
+		dz = z * dz * 2.;
+		z = z * z + c;
+
+		if (z - z0).norm() <= 1e-10 * (1. + z0.norm()) {
+			break;
+		}
+	}
+
+	// The cycle must close and be attracting for c to be inside its component
+	if (z - z0).norm() > 1e-10 * (1. + z0.norm()) || dz.norm_sqr() >= 1. {
+		return None;
+	}
+
+	let b = (1. - dz.norm_sqr()) / (dcdz + dzdz * dc / (one - dz)).norm();
+	(b.is_finite() && b > 0.).then_some(b / 4.)
+}
+
+pub fn calculate_pixel<T>(x: u32, y: u32, transform: &PlaneTransform<T>, max_iterations: Option<u32>) -> Rgb where T: Scalar {
+	fractal::calculate_pixel(&Mandelbrot::default(), &colouring::map_to_colour_loop, x, y, transform, max_iterations)
+}
+
+/// Returns the point c of the pixel at (x, y) of a mandelbrot set rendered with `transform`, which is the constant of the julia set that the mandelbrot set shows at that pixel, e.g. for `FractalType::JuliaSet { c }`
+///
+/// The julia set is connected if c is in the mandelbrot set, and looks like the mandelbrot set near c if c is on its boundary
+pub fn julia_parameter<T>(x: u32, y: u32, transform: &PlaneTransform<T>) -> Complex<f64> where T: Scalar {
+	// The same mapping as fractal::calculate_escape_data
+	let (cx, cy) = transform.transform((T::from_f64(x as f64), T::from_f64(y as f64)));
+
+	Complex::new(cx.to_f64(), cy.to_f64())
+}
+
+#[test]
+#[cfg(test)]
+fn test_interior_checks() {
+	use super::{Viewport, cpu_renderer::{self, Threadedness}};
+
+	assert_eq!(check_cardioid_bulb(Complex::new(0., 0.)), Some(ShortCircuit::Cardioid));
+	assert_eq!(check_cardioid_bulb(Complex::new(-1., 0.1)), Some(ShortCircuit::Bulb));
+	assert_eq!(check_cardioid_bulb(Complex::new(0.3, 0.)), None);
+
+	// The whole set contains every kind of interior pixel, and the checks should only change the escape counts of a few pixels on the boundary
+	let dims = (120, 90);
+	let transform = Viewport::new(Complex::new(-0.5, 0.), 1.2).to_transform(dims);
+	let unchecked = cpu_renderer::compute_iterations(Threadedness::Singlethreaded, &Mandelbrot::default(), dims, &transform, Some(1000), false);
+	let checked = cpu_renderer::compute_iterations(Threadedness::Singlethreaded, &Mandelbrot::default().interior_checks(InteriorChecks::all()), dims, &transform, Some(1000), false);
+
+	let counts = checked.short_circuits();
+	assert!(counts.cardioid > 0 && counts.bulb > 0 && counts.periodic > 0);
+	assert_eq!(unchecked.short_circuits().total(), 0);
+
+	let matching = checked.data.iter().zip(&unchecked.data).filter(|(a, b)| a.iterations == b.iterations).count();
+	assert!(matching as f64 >= 0.999 * unchecked.data.len() as f64);
+}
+
+#[test]
+#[cfg(test)]
+fn test_interior_distance() {
+	use std::f64::consts::TAU;
+
+	// The bound around the centres of the main cardioid and the period-2 bulb, whose boundaries are 0.25 away
+	assert!(interior_distance(Complex::new(0., 0.), 1000).is_some_and(|distance| distance > 0.05 && distance <= 0.25));
+	assert!(interior_distance(Complex::new(-1., 0.), 1000).is_some_and(|distance| distance > 0.05 && distance <= 0.25));
+
+	// Points outside the set, and points too close to the boundary for their cycle to be found, are not proven to be inside
+	assert_eq!(interior_distance(Complex::new(0.3, 0.), 1000), None);
+	assert_eq!(interior_distance(Complex::new(-0.75, 0.001), 1000), None);
+
+	// Every point within the bound is inside the set, including in small components of higher periods
+	for c in [Complex::new(-0.1, 0.3), Complex::new(-1.3, 0.02), Complex::new(-0.122, 0.745), Complex::new(-1.7548776662466927, 0.), Complex::new(-0.15652016, 1.03224711)] {
+		let distance = interior_distance(c, 5000).unwrap();
+		for k in 0..32 {
+			let point = c + Complex::from_polar(distance, k as f64 * TAU / 32.);
+			assert!(!Fractal::<f64>::iterate(&Mandelbrot::default(), point, 5000).escaped);
+		}
 	}
+}
+
+#[test]
+#[cfg(test)]
+fn test_julia_parameter() {
+	use super::{Viewport, julia::Julia};
 
-	colouring::map_to_colour_loop(i as f64, max_iterations as f64)
-}
\ No newline at end of file
+	let dims = (90, 60);
+	let viewport = Viewport::new(Complex::new(-0.75, 0.1), 0.5);
+	let transform = viewport.to_transform(dims);
+
+	// The centre pixel is the centre of the viewport
+	let centre = julia_parameter(45, 30, &transform);
+	assert!((centre - viewport.centre).norm() < 1e-2);
+
+	// The mandelbrot set at c is the julia set for c iterated from 0, so each pixel should escape after the same number of iterations
+	for (x, y) in [(0, 0), (45, 30), (10, 50), (89, 59)] {
+		let c = julia_parameter(x, y, &transform);
+		let julia = Fractal::<f64>::iterate(&Julia::new(c), Complex::new(0., 0.), 500);
+		let mandelbrot = fractal::calculate_escape_data(&Mandelbrot::default(), x, y, &transform, 500, false);
+		assert_eq!(julia.iterations, mandelbrot.iterations);
+	}
+}
@@ -0,0 +1,199 @@
+//! This module contains the `Multibrot` fractal, which generalises the mandelbrot set to z = z^d + c for any real exponent d, and the `Multijulia` fractal for its julia sets
+//!
+//! Non-negative integer exponents are calculated by repeated multiplication in the precision of the point, and other exponents (negative or fractional) with the polar form in f64, using the principal branch of the argument in (-pi, pi], so that the branch cut is along the negative real axis
+
+use super::common::{PlaneTransform, Scalar};
+use super::fractal::{self, Fractal, EscapeData};
+use color_space::Rgb;
+use num::Complex;
+use std::f64::consts::PI;
+use super::colouring;
+
+/// z^d doesn't grow faster than z for exponents of at most 1, so no escape radius guarantees that a point escapes. This large radius is used for them instead, which with negative exponents is passed by points that land close to 0
+pub const LOW_EXPONENT_ESCAPE_RADIUS: f64 = 1e4;
+
+/// The multibrot set for the exponent d, iterating z = z^d + c where c is the point being iterated
+///
+/// Iterating starts from z = c after one iteration, as z = 0 would give 0^d, which is infinite for negative exponents. For positive exponents this is the same as starting from z = 0
+#[derive(Clone, Copy, Debug)]
+pub struct Multibrot {
+	pub exponent: f64
+}
+
+impl Multibrot {
+	pub fn new(exponent: f64) -> Self {
+		Multibrot { exponent }
+	}
+}
+
+impl<T> Fractal<T> for Multibrot where T: Scalar {
+	fn iterate(&self, point: Complex<T>, max_iterations: u32) -> EscapeData {
+		iterate(self.exponent, point, point, max_iterations.min(1), 1., Fractal::<T>::escape_radius(self), max_iterations, false)
+	}
+
+	fn iterate_with_derivative(&self, point: Complex<T>, max_iterations: u32) -> EscapeData {
+		iterate(self.exponent, point, point, max_iterations.min(1), 1., Fractal::<T>::escape_radius(self), max_iterations, true)
+	}
+
+	fn escape_radius(&self) -> f64 {
+		escape_radius(self.exponent, 0.)
+	}
+
+	fn degree(&self) -> f64 {
+		degree(self.exponent)
+	}
+}
+
+/// The julia set of the multibrot set for the exponent d and the constant c, iterating z = z^d + c starting from z = the point being iterated
+#[derive(Clone, Copy, Debug)]
+pub struct Multijulia {
+	pub exponent: f64,
+	pub c: Complex<f64>
+}
+
+impl Multijulia {
+	pub fn new(exponent: f64, c: Complex<f64>) -> Self {
+		Multijulia { exponent, c }
+	}
+}
+
+impl<T> Fractal<T> for Multijulia where T: Scalar {
+	fn iterate(&self, point: Complex<T>, max_iterations: u32) -> EscapeData {
+		let c = Complex::new(T::from_f64(self.c.re), T::from_f64(self.c.im));
+		iterate(self.exponent, point, c, 0, 0., Fractal::<T>::escape_radius(self), max_iterations, false)
+	}
+
+	fn iterate_with_derivative(&self, point: Complex<T>, max_iterations: u32) -> EscapeData {
+		let c = Complex::new(T::from_f64(self.c.re), T::from_f64(self.c.im));
+		iterate(self.exponent, point, c, 0, 0., Fractal::<T>::escape_radius(self), max_iterations, true)
+	}
+
+	fn escape_radius(&self) -> f64 {
+		escape_radius(self.exponent, self.c.norm())
+	}
+
+	fn degree(&self) -> f64 {
+		degree(self.exponent)
+	}
+}
+
+pub fn calculate_pixel<T>(exponent: f64, x: u32, y: u32, transform: &PlaneTransform<T>, max_iterations: Option<u32>) -> Rgb where T: Scalar {
+	fractal::calculate_pixel(&Multibrot::new(exponent), &colouring::map_to_colour, x, y, transform, max_iterations)
+}
+
+/// Returns the radius beyond which z = z^d + c is sure to escape, for the exponent d and |c| at most `c_norm` (or any c for the multibrot set, as points with |c| beyond the radius escape on the first iteration)
+///
+/// For d > 1, |z^d + c| > |z| when |z| > 2^(1 / (d - 1)) and |z| >= |c|, so the radius is the larger of those and 2. For d <= 1 it is `LOW_EXPONENT_ESCAPE_RADIUS`
+pub fn escape_radius(exponent: f64, c_norm: f64) -> f64 {
+	if exponent > 1. {
+		2f64.max(2f64.powf(1. / (exponent - 1.))).max(c_norm)
+	} else {
+		LOW_EXPONENT_ESCAPE_RADIUS
+	}
+}
+
+/// The degree used for smooth colouring. For d <= 1, z doesn't grow by a power each iteration so there is no smooth iteration count, and an infinite degree makes smooth colouring give the iteration count
+fn degree(exponent: f64) -> f64 {
+	if exponent > 1. { exponent } else { f64::INFINITY }
+}
+
+/// Returns z^exponent, by repeated multiplication for non-negative integer exponents, or otherwise with the polar form in f64
+pub fn pow<T>(z: Complex<T>, exponent: f64) -> Complex<T> where T: Scalar {
+	if exponent >= 0. && exponent.fract() == 0. && exponent <= u32::MAX as f64 {
+		powu(z, exponent as u32)
+	} else {
+		let z = powf(Complex::new(z.re.to_f64(), z.im.to_f64()), exponent);
+		Complex::new(T::from_f64(z.re), T::from_f64(z.im))
+	}
+}
+
+/// Returns z^n by exponentiation by squaring
+fn powu<T>(z: Complex<T>, n: u32) -> Complex<T> where T: Scalar {
+	let mul = |a: Complex<T>, b: Complex<T>| Complex::new(a.re * b.re - a.im * b.im, a.re * b.im + a.im * b.re);
+
+	let mut result = Complex::new(T::one(), T::zero());
+	let mut base = z;
+	let mut n = n;
+
+	while n > 0 {
+		if n & 1 == 1 {
+			result = mul(result, base);
+		}
+		n >>= 1;
+		if n > 0 {
+			base = mul(base, base);
+		}
+	}
+
+	result
+}
+
+/// Returns z^exponent on the principal branch, with the argument of z in (-pi, pi]. 0 to a negative exponent is infinite
+fn powf(z: Complex<f64>, exponent: f64) -> Complex<f64> {
+	if z.re == 0. && z.im == 0. {
+		return if exponent > 0. { Complex::new(0., 0.) } else { Complex::new(f64::INFINITY, 0.) };
+	}
+
+	// atan2 gives -pi for a negative real z with an imaginary part of -0, which is on the other side of the branch cut
+	let arg = if z.im == 0. && z.re < 0. { PI } else { z.im.atan2(z.re) };
+
+	Complex::from_polar(z.norm().powf(exponent), exponent * arg)
+}
+
+/// Iterates z = z^exponent + c from z = `z`, after `iterations` iterations have been done, until |z| passes `escape_radius` or `max_iterations` is reached, also tracking the derivative if `track_derivative` is true. The derivative starts at 1 and `dc` is added to it each iteration, which is 1 for the derivative with respect to c and 0 with respect to the initial z
+#[allow(clippy::too_many_arguments)]
+fn iterate<T>(exponent: f64, z: Complex<T>, c: Complex<T>, iterations: u32, dc: f64, escape_radius: f64, max_iterations: u32, track_derivative: bool) -> EscapeData where T: Scalar {
+	let escape_radius_sqr = T::from_f64(escape_radius * escape_radius);
+	let mut z = z;
+	let mut dz = Complex::new(1., 0.);
+
+	let mut i = iterations;
+	while i < max_iterations && z.re * z.re + z.im * z.im <= escape_radius_sqr {
+		if track_derivative {
+			// dz = d * z^(d - 1) * dz + dc
+			dz = pow(Complex::new(z.re.to_f64(), z.im.to_f64()), exponent - 1.) * exponent * dz + dc;
+		}
+		let power = pow(z, exponent);
+		z = Complex::new(power.re + c.re, power.im + c.im);
+		i += 1;
+	}
+
+	EscapeData {
+		iterations: i,
+		escaped: i < max_iterations,
+		final_z: Complex::new(z.re.to_f64(), z.im.to_f64()),
+		derivative: track_derivative.then_some(dz),
+		short_circuit: None
+	}
+}
+
+#[test]
+#[cfg(test)]
+fn test_multibrot() {
+	use super::mandelbrot::Mandelbrot;
+	use super::julia::{self, Julia};
+
+	// An exponent of 2 gives exactly the mandelbrot and julia sets
+	for y in -12..=12 {
+		for x in -20..=8 {
+			let point = Complex::new(x as f64 * 0.1, y as f64 * 0.1);
+			assert_eq!(Fractal::<f64>::iterate(&Multibrot::new(2.), point, 200).iterations, Fractal::<f64>::iterate(&Mandelbrot::default(), point, 200).iterations);
+			assert_eq!(Fractal::<f64>::iterate(&Multijulia::new(2., julia::DEFAULT_C), point, 200).iterations, Fractal::<f64>::iterate(&Julia::default(), point, 200).iterations);
+		}
+	}
+
+	// Repeated multiplication agrees with the polar form
+	let z = Complex::new(-0.7, 0.45);
+	for n in [0, 1, 3, 5, 8] {
+		assert!((pow(z, n as f64) - powf(z, n as f64)).norm() < 1e-12);
+	}
+	assert!((pow(z, -3.) * pow(z, 3.) - Complex::new(1., 0.)).norm() < 1e-12);
+
+	// Both signs of zero on the negative real axis are on the same side of the branch cut
+	assert!((pow(Complex::new(-4., 0.), 0.5) - Complex::new(0., 2.)).norm() < 1e-12);
+	assert!((pow(Complex::new(-4., -0.), 0.5) - Complex::new(0., 2.)).norm() < 1e-12);
+
+	assert_eq!(escape_radius(3., 0.), 2.);
+	assert_eq!(escape_radius(1.5, 0.), 4.);
+	assert_eq!(escape_radius(-2., 0.), LOW_EXPONENT_ESCAPE_RADIUS);
+}
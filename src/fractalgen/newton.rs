@@ -0,0 +1,448 @@
+//! This module contains the `Newton` fractal, the basins of attraction of the roots of a polynomial under Newton's method (or Halley's, Chebyshev's or Householder's), and the `RootColouring` colour map that colours each pixel by the root it converges to, shaded by how many iterations it took
+//!
+//! It also contains the `Nova` fractal, which adds the point being iterated to each step of Newton's method, like c in the mandelbrot set
+//!
+//! Both are `Fractal`s, so they can be rendered by any of the CPU renderers with any `PlaneTransform` and output format. They are calculated in f64 whatever the precision of the transform
+
+use super::common::{PlaneTransform, Scalar};
+use super::fractal::{Fractal, EscapeData};
+use super::colouring::ColourMap;
+use super::cpu_renderer::{self, Threadedness};
+use super::ImageBufferFormat;
+use color_space::{Rgb, Hsv};
+use image::RgbImage;
+use num::Complex;
+
+/// The distance from a root within which a point is considered to have converged to it, when none is given
+pub const DEFAULT_TOLERANCE: f64 = 1e-6;
+
+/// The fraction of brightness lost for each iteration taken to converge, when none is given
+pub const DEFAULT_SHADING: f64 = 0.04;
+
+/// A polynomial with complex coefficients
+#[derive(Clone, Debug, PartialEq)]
+pub struct Polynomial {
+	/// The coefficients, from the constant term up to the coefficient of the highest power
+	pub coefficients: Vec<Complex<f64>>
+}
+
+impl Polynomial {
+	/// The polynomial with `coefficients`, from the constant term up. Zero coefficients of the highest powers are removed
+	pub fn new(coefficients: Vec<Complex<f64>>) -> Self {
+		let mut coefficients = coefficients;
+		while coefficients.last().is_some_and(|c| *c == Complex::new(0., 0.)) {
+			coefficients.pop();
+		}
+
+		Polynomial { coefficients }
+	}
+
+	/// The monic polynomial with `roots`, i.e. the product of (z - root) for each root
+	pub fn from_roots(roots: &[Complex<f64>]) -> Self {
+		let mut coefficients = vec![Complex::new(1., 0.)];
+
+		for root in roots {
+			// Multiplying by (z - root) shifts each coefficient up a power and subtracts root times it
+			let mut next = vec![Complex::new(0., 0.); coefficients.len() + 1];
+			for (i, coefficient) in coefficients.iter().enumerate() {
+				next[i + 1] += coefficient;
+				next[i] -= coefficient * root;
+			}
+			coefficients = next;
+		}
+
+		Polynomial { coefficients }
+	}
+
+	/// The degree of the polynomial, taking the zero polynomial to have degree 0
+	pub fn degree(&self) -> usize {
+		self.coefficients.len().saturating_sub(1)
+	}
+
+	/// Returns p(z), p'(z), p''(z) and p'''(z), evaluated together with Horner's method
+	pub fn evaluate(&self, z: Complex<f64>) -> (Complex<f64>, Complex<f64>, Complex<f64>, Complex<f64>) {
+		let zero = Complex::new(0., 0.);
+		let (mut p, mut dp, mut ddp, mut dddp) = (zero, zero, zero, zero);
+
+		for coefficient in self.coefficients.iter().rev() {
+			dddp = dddp * z + ddp * 3.;
+			ddp = ddp * z + dp * 2.;
+			dp = dp * z + p;
+			p = p * z + coefficient;
+		}
+
+		(p, dp, ddp, dddp)
+	}
+
+	/// Finds all the roots of the polynomial (with repeated roots repeated) with the Durand-Kerner method
+	pub fn roots(&self) -> Vec<Complex<f64>> {
+		let degree = self.degree();
+		if degree == 0 {
+			return Vec::new();
+		}
+
+		let leading = self.coefficients[degree];
+		let monic = Polynomial::new(self.coefficients.iter().map(|c| c / leading).collect());
+
+		// The starting points must not be symmetric, so they are powers of a number that is neither real nor a root of unity
+		let seed = Complex::new(0.4, 0.9);
+		let mut roots: Vec<Complex<f64>> = (0..degree).map(|i| seed.powu(i as u32)).collect();
+
+		for _ in 0..1000 {
+			let mut max_change: f64 = 0.;
+
+			for i in 0..degree {
+				let denominator = (0..degree).filter(|&j| j != i).fold(Complex::new(1., 0.), |product, j| product * (roots[i] - roots[j]));
+				let change = monic.evaluate(roots[i]).0 / denominator;
+
+				if change.is_finite() {
+					roots[i] -= change;
+					max_change = max_change.max(change.norm());
+				}
+			}
+
+			if max_change < 1e-15 {
+				break;
+			}
+		}
+
+		roots
+	}
+}
+
+/// The root-finding method iterated by `Newton` and `Nova`. Each gives the step that is subtracted from z
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Method {
+	/// Newton's method, stepping p / p'. Converges quadratically
+	#[default]
+	Newton,
+	/// Halley's method, stepping 2pp' / (2p'^2 - pp''), which is Householder's method of order 2. Converges cubically
+	Halley,
+	/// Chebyshev's method, stepping (p / p')(1 + pp'' / 2p'^2). Converges cubically
+	Chebyshev,
+	/// Householder's method of order 3, stepping -3 (1/p)'' / (1/p)''' = (6pp'^2 - 3p^2p'') / (6p'^3 - 6pp'p'' + p^2p'''). Converges quartically
+	Householder
+}
+
+impl Method {
+	/// Returns the step to subtract from z, given p(z), p'(z), p''(z) and p'''(z)
+	pub fn step(&self, p: Complex<f64>, dp: Complex<f64>, ddp: Complex<f64>, dddp: Complex<f64>) -> Complex<f64> {
+		match self {
+			Method::Newton => p / dp,
+			Method::Halley => (p * dp * 2.) / (dp * dp * 2. - p * ddp),
+			Method::Chebyshev => (p / dp) * (Complex::new(1., 0.) + (p * ddp) / (dp * dp * 2.)),
+			Method::Householder => (p * dp * dp * 6. - p * p * ddp * 3.) / (dp * dp * dp * 6. - p * dp * ddp * 6. + p * p * dddp)
+		}
+	}
+}
+
+/// The basins of attraction of the roots of a polynomial, iterating z = z - a * step(z) starting from z = the point being iterated, where step is given by the `Method` and a is the relaxation
+///
+/// Points that come within the tolerance of a root are reported as escaped, with the final z near that root, which `RootColouring` colours them by. Points that don't converge within the max iterations are reported as not escaped
+#[derive(Clone, Debug)]
+pub struct Newton {
+	pub polynomial: Polynomial,
+	/// The roots of the polynomial, which points are checked for convergence to
+	pub roots: Vec<Complex<f64>>,
+	pub method: Method,
+	/// The factor a that each step is multiplied by, 1 for the unrelaxed method. Values between 0 and 1 damp the steps, converging more slowly with thicker boundaries between basins, and other values (including complex ones) give different shapes
+	pub relaxation: Complex<f64>,
+	pub tolerance: f64
+}
+
+impl Newton {
+	/// Newton's method for `polynomial`, finding its roots with `Polynomial::roots`
+	pub fn new(polynomial: Polynomial) -> Self {
+		let roots = polynomial.roots();
+
+		Newton {
+			polynomial,
+			roots,
+			method: Method::Newton,
+			relaxation: Complex::new(1., 0.),
+			tolerance: DEFAULT_TOLERANCE
+		}
+	}
+
+	/// Newton's method for the polynomial with `roots`, which are used exactly
+	pub fn from_roots(roots: Vec<Complex<f64>>) -> Self {
+		Newton {
+			polynomial: Polynomial::from_roots(&roots),
+			roots,
+			method: Method::Newton,
+			relaxation: Complex::new(1., 0.),
+			tolerance: DEFAULT_TOLERANCE
+		}
+	}
+
+	/// Builder-style method
+	pub fn method(mut self, method: Method) -> Self {
+		self.method = method;
+		self
+	}
+
+	/// Builder-style method
+	pub fn relaxation(mut self, relaxation: Complex<f64>) -> Self {
+		self.relaxation = relaxation;
+		self
+	}
+
+	/// Builder-style method
+	pub fn tolerance(mut self, tolerance: f64) -> Self {
+		self.tolerance = tolerance;
+		self
+	}
+
+	/// Returns the index of the root that `z` is within the tolerance of, if any
+	pub fn converged_root(&self, z: Complex<f64>) -> Option<usize> {
+		self.roots.iter().position(|root| (z - root).norm_sqr() <= self.tolerance * self.tolerance)
+	}
+
+	/// Colouring by the roots of the polynomial, with evenly spaced hues and `DEFAULT_SHADING`
+	pub fn root_colouring(&self) -> RootColouring {
+		RootColouring::new(self.roots.clone())
+	}
+}
+
+impl<T> Fractal<T> for Newton where T: Scalar {
+	fn iterate(&self, point: Complex<T>, max_iterations: u32) -> EscapeData {
+		let mut z = Complex::new(point.re.to_f64(), point.im.to_f64());
+
+		let mut i = 0;
+		let mut converged = self.converged_root(z).is_some();
+		while i < max_iterations && !converged {
+			let (p, dp, ddp, dddp) = self.polynomial.evaluate(z);
+			z -= self.relaxation * self.method.step(p, dp, ddp, dddp);
+			i += 1;
+
+			// A step from where p' is 0 is infinite, and never converges
+			if !z.is_finite() {
+				break;
+			}
+			converged = self.converged_root(z).is_some();
+		}
+
+		EscapeData {
+			iterations: i,
+			escaped: converged,
+			final_z: z,
+			derivative: None,
+			short_circuit: None
+		}
+	}
+}
+
+/// The Nova fractal, iterating z = z - a * step(z) + c starting from z = `start`, where c is the point being iterated, step is given by the `Method` and a is the relaxation
+///
+/// Points whose orbit converges (z moves less than the tolerance in an iteration) are reported as escaped after the number of iterations taken, so they can be coloured with any colour map, and points that don't converge within the max iterations are reported as not escaped
+#[derive(Clone, Debug)]
+pub struct Nova {
+	pub polynomial: Polynomial,
+	pub method: Method,
+	/// The factor a that each step is multiplied by
+	pub relaxation: Complex<f64>,
+	pub tolerance: f64,
+	/// The initial z, which should be a critical point of the step so that the image shows the whole parameter space, such as a root of the polynomial. For the classic z^3 - 1 this is 1
+	pub start: Complex<f64>
+}
+
+impl Nova {
+	/// The Nova fractal for `polynomial`, unrelaxed and starting from z = 1
+	pub fn new(polynomial: Polynomial) -> Self {
+		Nova {
+			polynomial,
+			method: Method::Newton,
+			relaxation: Complex::new(1., 0.),
+			tolerance: DEFAULT_TOLERANCE,
+			start: Complex::new(1., 0.)
+		}
+	}
+
+	/// Builder-style method
+	pub fn method(mut self, method: Method) -> Self {
+		self.method = method;
+		self
+	}
+
+	/// Builder-style method
+	pub fn relaxation(mut self, relaxation: Complex<f64>) -> Self {
+		self.relaxation = relaxation;
+		self
+	}
+
+	/// Builder-style method
+	pub fn tolerance(mut self, tolerance: f64) -> Self {
+		self.tolerance = tolerance;
+		self
+	}
+
+	/// Builder-style method
+	pub fn start(mut self, start: Complex<f64>) -> Self {
+		self.start = start;
+		self
+	}
+}
+
+impl<T> Fractal<T> for Nova where T: Scalar {
+	fn iterate(&self, point: Complex<T>, max_iterations: u32) -> EscapeData {
+		let c = Complex::new(point.re.to_f64(), point.im.to_f64());
+		let mut z = self.start;
+
+		let mut i = 0;
+		let mut converged = false;
+		while i < max_iterations && !converged {
+			let (p, dp, ddp, dddp) = self.polynomial.evaluate(z);
+			let next = z - self.relaxation * self.method.step(p, dp, ddp, dddp) + c;
+			i += 1;
+
+			if !next.is_finite() {
+				break;
+			}
+			converged = (next - z).norm_sqr() <= self.tolerance * self.tolerance;
+			z = next;
+		}
+
+		EscapeData {
+			iterations: i,
+			escaped: converged,
+			final_z: z,
+			derivative: None,
+			short_circuit: None
+		}
+	}
+}
+
+/// Colours points by the root their final z is nearest to, shaded darker the more iterations they took to converge, and points that didn't converge black
+#[derive(Clone, Debug, PartialEq)]
+pub struct RootColouring {
+	pub roots: Vec<Complex<f64>>,
+	/// The colour of each root, repeated if there are more roots than colours
+	pub colours: Vec<Rgb>,
+	/// The fraction of brightness lost for each iteration, from 0 for no shading to 1
+	pub shading: f64
+}
+
+impl RootColouring {
+	/// Colouring by `roots` with evenly spaced hues and `DEFAULT_SHADING`
+	pub fn new(roots: Vec<Complex<f64>>) -> Self {
+		let colours = (0..roots.len()).map(|i| Rgb::from(Hsv::new(i as f64 * 360. / roots.len() as f64, 1., 1.))).collect();
+
+		RootColouring {
+			roots,
+			colours,
+			shading: DEFAULT_SHADING
+		}
+	}
+
+	/// Builder-style method
+	pub fn colours(mut self, colours: Vec<Rgb>) -> Self {
+		self.colours = colours;
+		self
+	}
+
+	/// Builder-style method
+	pub fn shading(mut self, shading: f64) -> Self {
+		self.shading = shading;
+		self
+	}
+
+	/// Returns `colour` darkened for taking `iterations` iterations
+	fn shade(&self, colour: Rgb, iterations: f64) -> Rgb {
+		let brightness = (1. - self.shading.clamp(0., 1.)).powf(iterations);
+		Rgb::new(colour.r * brightness, colour.g * brightness, colour.b * brightness)
+	}
+}
+
+impl ColourMap for RootColouring {
+	/// Without the final z there is no root to colour by, so shades white by the number of iterations
+	fn map(&self, iterations: f64, max_iterations: f64) -> Rgb {
+		if iterations == max_iterations {
+			Rgb::new(0., 0., 0.)
+		} else {
+			self.shade(Rgb::new(255., 255., 255.), iterations)
+		}
+	}
+
+	fn colour(&self, data: &EscapeData, max_iterations: u32) -> Rgb {
+		let nearest = self.roots.iter().enumerate().min_by(|(_, a), (_, b)| (data.final_z - *a).norm_sqr().total_cmp(&(data.final_z - *b).norm_sqr()));
+
+		match nearest {
+			Some((i, _)) if data.escaped && !self.colours.is_empty() => self.shade(self.colours[i % self.colours.len()], data.iterations as f64),
+			_ => self.map(data.iterations as f64, max_iterations as f64)
+		}
+	}
+}
+
+/// Generates an RGB image of `newton` coloured by root with `Newton::root_colouring`, with given dimensions, and a defined transformation from the image coordinate plane to the complex plane, and the max_iterations is how many iterations points have to converge (default 100)
+///
+/// Specify using `threadedness` whether to calculate using one thread or multiple
+pub fn generate_fractal_image<T>(threadedness: Threadedness, newton: &Newton, dimensions: (u32, u32), transform: &PlaneTransform<T>, max_iterations: Option<u32>) -> RgbImage where T: Scalar {
+	cpu_renderer::generate_fractal_image(threadedness, newton, &newton.root_colouring(), dimensions, transform, max_iterations)
+}
+
+/// Generates an RGB image of `newton` coloured by root with `Newton::root_colouring`, with given dimensions, and a defined transformation from the image coordinate plane to the complex plane, and the max_iterations is how many iterations points have to converge (default 100)
+///
+/// The RGB image is written to the buffer contained within img_buffer_fmt, in the format specified. Specify using `threadedness` whether to calculate using one thread or multiple
+pub fn render_fractal_to<T>(threadedness: Threadedness, img_buffer_fmt: ImageBufferFormat, newton: &Newton, dimensions: (u32, u32), transform: &PlaneTransform<T>, max_iterations: Option<u32>) where T: Scalar {
+	cpu_renderer::render_fractal_to(threadedness, img_buffer_fmt, newton, &newton.root_colouring(), dimensions, transform, max_iterations)
+}
+
+#[test]
+#[cfg(test)]
+fn test_newton() {
+	use std::f64::consts::PI;
+
+	// The cube roots of unity
+	let roots: Vec<Complex<f64>> = (0..3).map(|k| Complex::from_polar(1., k as f64 * 2. * PI / 3.)).collect();
+	let polynomial = Polynomial::new(vec![Complex::new(-1., 0.), Complex::new(0., 0.), Complex::new(0., 0.), Complex::new(1., 0.), Complex::new(0., 0.)]);
+
+	assert_eq!(polynomial.degree(), 3);
+	assert!(Polynomial::from_roots(&roots).coefficients.iter().zip(&polynomial.coefficients).all(|(a, b)| (a - b).norm() < 1e-12));
+	assert!(roots.iter().all(|root| polynomial.roots().iter().any(|found| (found - root).norm() < 1e-10)));
+
+	for method in [Method::Newton, Method::Halley, Method::Chebyshev, Method::Householder] {
+		let newton = Newton::new(polynomial.clone()).method(method);
+
+		// Points near a root converge to it, and the fixed point where p' is exactly 0 never converges
+		for (i, root) in newton.roots.iter().enumerate() {
+			let data = Fractal::<f64>::iterate(&newton, root * 1.2, 100);
+			assert!(data.escaped && newton.converged_root(data.final_z) == Some(i));
+		}
+		assert!(!Fractal::<f64>::iterate(&newton, Complex::new(0., 0.), 100).escaped);
+
+		// Damped steps take longer to converge
+		let relaxed = newton.clone().relaxation(Complex::new(0.5, 0.));
+		assert!(Fractal::<f64>::iterate(&relaxed, newton.roots[1] * 1.2, 100).iterations > Fractal::<f64>::iterate(&newton, newton.roots[1] * 1.2, 100).iterations);
+	}
+
+	// Steps from z = 1 towards the cube root of 2, calculated by hand from p = -1, p' = 3, p'' = 6 and p''' = 6
+	let cube_root = Polynomial::new(vec![Complex::new(-2., 0.), Complex::new(0., 0.), Complex::new(0., 0.), Complex::new(1., 0.)]);
+	let (p, dp, ddp, dddp) = cube_root.evaluate(Complex::new(1., 0.));
+	assert_eq!((p, dp, ddp, dddp), (Complex::new(-1., 0.), Complex::new(3., 0.), Complex::new(6., 0.), Complex::new(6., 0.)));
+	for (method, step) in [(Method::Newton, -1. / 3.), (Method::Halley, -0.25), (Method::Chebyshev, -1. / 3. * 2. / 3.), (Method::Householder, -72. / 276.)] {
+		assert!((method.step(p, dp, ddp, dddp) - step).norm() < 1e-12);
+	}
+
+	// The error after a step is about the error before it to the power of the order of convergence, so halving the error before a step divides the error after it by 2^order
+	let root = 2f64.cbrt();
+	for (method, order) in [(Method::Newton, 2.), (Method::Halley, 3.), (Method::Chebyshev, 3.), (Method::Householder, 4.)] {
+		let error_after_step = |error: f64| {
+			let (p, dp, ddp, dddp) = cube_root.evaluate(Complex::new(root + error, 0.));
+			(error - method.step(p, dp, ddp, dddp).re).abs()
+		};
+		assert!(((error_after_step(0.02) / error_after_step(0.01)).log2() - order).abs() < 0.1);
+	}
+
+	// Every root has a basin in the image, coloured by its hue
+	let newton = Newton::new(polynomial.clone());
+	let transform = PlaneTransform::new().scale((0.05, 0.05)).base_offset((30., 30.));
+	let img = generate_fractal_image(Threadedness::Singlethreaded, &newton, (60, 60), &transform, Some(50));
+	let colours = newton.root_colouring().colours;
+	for colour in colours {
+		let hue = |r: f64, g: f64, b: f64| Hsv::from(Rgb::new(r, g, b)).h.round();
+		assert!(img.pixels().any(|px| px.0 != [0, 0, 0] && hue(px.0[0] as f64, px.0[1] as f64, px.0[2] as f64) == hue(colour.r, colour.g, colour.b)));
+	}
+
+	// Nova at c = 0 starts on the root 1, so converges straight away
+	assert_eq!(Fractal::<f64>::iterate(&Nova::new(polynomial), Complex::new(0., 0.), 100).iterations, 1);
+}
@@ -0,0 +1,609 @@
+//! This module contains a deep zoom renderer for the mandelbrot set, which uses perturbation theory to render zooms far beyond the precision of f64
+//!
+//! One reference orbit Z is calculated in arbitrary precision (see `BigFloat`), and each pixel c = C + dc only iterates its difference from the reference in f64, with dz' = 2Zdz + dz^2 + dc. Where the reference is not representative of a pixel (a glitch), the pixel is either rebased onto the start of the reference orbit, or detected with Pauldelbrot's criterion and recalculated using a secondary reference orbit
+//!
+//! The first iterations of every pixel are usually skipped with series approximation, which approximates dz after n iterations as a polynomial in dc whose truncation error is bounded rigorously (see `SeriesApproximation`)
+//!
+//! The differences are stored in f64, so zooms work until the pixel spacing approaches the smallest f64 (around 1e-300)
+
+use super::{PlaneTransform, Viewport, IterationBuffer, EscapeData, ImageBufferFormat, ChannelArrayFormat};
+use super::bigfloat::BigFloat;
+use super::double_double::DoubleDouble;
+use super::common::Scalar;
+use super::colouring::ColourMap;
+use super::cpu_renderer::{self, Threadedness};
+use super::fractal;
+use super::viewport::AspectMode;
+use image::RgbImage;
+use num::Complex;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+/// A `Viewport` whose centre is stored in arbitrary precision, for deep zooms
+#[derive(Clone, Debug, PartialEq)]
+pub struct DeepViewport {
+	/// The point in the complex plane at the centre of the image, as (real, imaginary)
+	pub centre: (BigFloat, BigFloat),
+	/// Half the size of the region, see `AspectMode` for which dimension this is
+	pub radius: f64,
+	/// The anticlockwise rotation of the region about its centre, in radians
+	pub rotation: f64,
+	pub aspect_mode: AspectMode
+}
+
+/// Options for how glitches are handled by the perturbation renderer
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PerturbationOptions {
+	/// Whether to rebase a pixel onto the start of the reference orbit whenever it gets closer to 0 than to the reference (or the reference escapes first), which avoids glitches without needing secondary references
+	pub rebasing: bool,
+	/// If rebasing is disabled, a pixel is glitched when |z|^2 < glitch_tolerance * |Z|^2 (Pauldelbrot's criterion), where z is the pixel's orbit and Z the reference's
+	pub glitch_tolerance: f64,
+	/// The max number of reference orbits, including the first, to calculate to fix glitched pixels when rebasing is disabled
+	pub max_references: u32,
+	/// The number of terms of the series approximation used to skip the first iterations of every pixel, or 0 to not skip any iterations
+	pub series_order: u32,
+	/// The max error of the series approximation relative to the value it approximates, beyond which no more iterations are skipped
+	pub series_tolerance: f64
+}
+
+/// Statistics about a perturbation render
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub struct PerturbationStats {
+	/// The number of reference orbits calculated
+	pub references: u32,
+	/// The total number of times pixels were rebased onto the start of the reference orbit
+	pub rebases: u64,
+	/// The number of pixels that were still glitched after the max number of references was reached
+	pub glitched_pixels: u64,
+	/// The number of iterations skipped with series approximation for every pixel iterated with the first reference
+	pub skipped_iterations: u32
+}
+
+/// The orbit of a reference point, calculated in arbitrary precision and stored in f64
+#[derive(Clone, Debug)]
+pub struct ReferenceOrbit {
+	/// The reference point, as (real, imaginary)
+	pub point: (BigFloat, BigFloat),
+	/// The values of z starting from z = 0, up to and including the first that escaped or the value after max iterations
+	pub orbit: Vec<Complex<f64>>
+}
+
+/// A series approximation of dz after `iterations` iterations as a polynomial in dc, which is valid for every |dc| up to `max_delta`
+///
+/// The coefficients are scaled by powers of `max_delta` so that they don't overflow at deep zooms, i.e. dz = sum of coefficients[k] * (dc / max_delta)^(k + 1). The number of iterations is chosen so that a rigorous bound on the truncation error stays within the tolerance for every such dc, and so that no such dc could have escaped
+#[derive(Clone, Debug)]
+pub struct SeriesApproximation {
+	/// The number of iterations that can be skipped
+	pub iterations: u32,
+	pub max_delta: f64,
+	pub coefficients: Vec<Complex<f64>>,
+	/// A bound on |error| / |dc / max_delta|^(order + 1), where error is the difference between dz and the polynomial
+	pub error_bound: f64
+}
+
+/// The result of iterating a single pixel with perturbation
+#[derive(Clone, Copy)]
+struct PixelResult {
+	data: EscapeData,
+	/// If the pixel was glitched, how badly (lower is worse), used to choose the next reference
+	glitch: Option<f64>,
+	rebases: u32
+}
+
+impl DeepViewport {
+	/// Creates an unrotated viewport where the whole circle of `radius` around `centre` is visible
+	pub fn new(centre: (BigFloat, BigFloat), radius: f64) -> Self {
+		DeepViewport {
+			centre,
+			radius,
+			rotation: 0.,
+			aspect_mode: AspectMode::Fit
+		}
+	}
+
+	/// Creates an unrotated viewport from the decimal strings of the real and imaginary parts of the centre (e.g. "-1.7499999999999999999999999999999999999999999"), with enough precision for `radius`. Returns None if either string is not a valid decimal number
+	pub fn parse(re: &str, im: &str, radius: f64) -> Option<Self> {
+		let precision = precision_for_radius(radius);
+
+		Some(DeepViewport::new((BigFloat::parse(re, precision)?, BigFloat::parse(im, precision)?), radius))
+	}
+
+	/// Creates a deep viewport describing the same region as `viewport`
+	pub fn from_viewport(viewport: &Viewport) -> Self {
+		let precision = precision_for_radius(viewport.radius);
+		let centre = (
+			BigFloat::from_f64(viewport.centre.re, precision).unwrap_or_else(|| BigFloat::zero(precision)),
+			BigFloat::from_f64(viewport.centre.im, precision).unwrap_or_else(|| BigFloat::zero(precision))
+		);
+
+		DeepViewport {
+			centre,
+			radius: viewport.radius,
+			rotation: viewport.rotation,
+			aspect_mode: viewport.aspect_mode
+		}
+	}
+
+	/// Builder-style method
+	///
+	/// Magnifies the viewport by `factor`, dividing the radius by it. The precision of the centre is increased to match
+	pub fn zoom_by(mut self, factor: f64) -> Self {
+		self.radius /= factor;
+		let precision = precision_for_radius(self.radius).max(self.centre.0.precision());
+		self.centre = (self.centre.0.with_precision(precision), self.centre.1.with_precision(precision));
+		self
+	}
+
+	/// Builder-style method
+	pub fn rotation(mut self, rotation: f64) -> Self {
+		self.rotation = rotation;
+		self
+	}
+
+	/// Builder-style method
+	pub fn aspect_mode(mut self, aspect_mode: AspectMode) -> Self {
+		self.aspect_mode = aspect_mode;
+		self
+	}
+
+	/// Returns the transform from the image coordinate plane of an image with `dimensions` to the offset in the complex plane from the centre
+	pub fn delta_transform(&self, dimensions: (u32, u32)) -> PlaneTransform<f64> {
+		Viewport {
+			centre: Complex::new(0., 0.),
+			radius: self.radius,
+			rotation: self.rotation,
+			aspect_mode: self.aspect_mode
+		}.to_transform(dimensions)
+	}
+
+	/// Returns the transform from the image coordinate plane of an image with `dimensions` to the complex plane in double-double precision, so the viewport can be rendered directly by the CPU renderers without perturbation, e.g. to check the output of the perturbation renderer. This is only exact for radii down to about 1e-20
+	pub fn double_double_transform(&self, dimensions: (u32, u32)) -> PlaneTransform<DoubleDouble> {
+		// Only the centre needs more precision than f64, the pixel spacing and rotation do not
+		let delta = self.delta_transform(dimensions);
+
+		PlaneTransform {
+			scale_x: DoubleDouble::from_f64(delta.scale_x),
+			scale_y: DoubleDouble::from_f64(delta.scale_y),
+			shear_x: DoubleDouble::from_f64(delta.shear_x),
+			shear_y: DoubleDouble::from_f64(delta.shear_y),
+			base_offset_x: DoubleDouble::from_f64(delta.base_offset_x),
+			base_offset_y: DoubleDouble::from_f64(delta.base_offset_y),
+			transformed_offset_x: -DoubleDouble::from_bigfloat(&self.centre.0),
+			transformed_offset_y: -DoubleDouble::from_bigfloat(&self.centre.1)
+		}
+	}
+}
+
+impl Default for PerturbationOptions {
+	fn default() -> Self {
+		PerturbationOptions {
+			rebasing: true,
+			glitch_tolerance: 1e-6,
+			max_references: 16,
+			series_order: 8,
+			series_tolerance: 1e-12
+		}
+	}
+}
+
+impl PerturbationOptions {
+	/// Builder-style method
+	pub fn rebasing(mut self, rebasing: bool) -> Self {
+		self.rebasing = rebasing;
+		self
+	}
+
+	/// Builder-style method
+	pub fn glitch_tolerance(mut self, glitch_tolerance: f64) -> Self {
+		self.glitch_tolerance = glitch_tolerance;
+		self
+	}
+
+	/// Builder-style method
+	pub fn max_references(mut self, max_references: u32) -> Self {
+		self.max_references = max_references;
+		self
+	}
+
+	/// Builder-style method
+	pub fn series_order(mut self, series_order: u32) -> Self {
+		self.series_order = series_order;
+		self
+	}
+
+	/// Builder-style method
+	pub fn series_tolerance(mut self, series_tolerance: f64) -> Self {
+		self.series_tolerance = series_tolerance;
+		self
+	}
+}
+
+impl ReferenceOrbit {
+	/// Calculates the orbit of `point` in arbitrary precision until it escapes or `max_iterations` is reached
+	pub fn new(point: (BigFloat, BigFloat), max_iterations: u32) -> Self {
+		let (cr, ci) = &point;
+		let precision = cr.precision().max(ci.precision());
+		let mut zr = BigFloat::zero(precision);
+		let mut zi = BigFloat::zero(precision);
+
+		let mut orbit = Vec::with_capacity(max_iterations as usize + 1);
+		orbit.push(Complex::new(0., 0.));
+
+		for _ in 0..max_iterations {
+			// z = z^2 + c
+			let zri = &zr * &zi;
+			let zr_new = &(&(&zr * &zr) - &(&zi * &zi)) + cr;
+			zi = &(&zri + &zri) + ci;
+			zr = zr_new;
+
+			let z = Complex::new(zr.to_f64(), zi.to_f64());
+			orbit.push(z);
+
+			if z.norm_sqr() > 4. {
+				break;
+			}
+		}
+
+		ReferenceOrbit {
+			point,
+			orbit
+		}
+	}
+}
+
+impl SeriesApproximation {
+	/// Calculates the series approximation with `order` terms for offsets up to `max_delta` from the reference point of `orbit`, skipping as many iterations as possible while the relative error is at most `tolerance`
+	pub fn new(orbit: &[Complex<f64>], order: u32, max_delta: f64, tolerance: f64) -> Self {
+		let order = order as usize;
+		let mut coefficients = vec![Complex::new(0., 0.); order];
+		let mut error_bound = 0.;
+		let mut iterations = 0;
+
+		if order == 0 || max_delta <= 0. || !max_delta.is_finite() {
+			return SeriesApproximation { iterations: 0, max_delta, coefficients, error_bound };
+		}
+
+		while iterations + 1 < orbit.len() {
+			let z = orbit[iterations];
+
+			// dz' = 2Zdz + dz^2 + dc, so each coefficient is 2Z times itself plus the terms of dz^2 of the same degree (and dc for the first)
+			let mut next: Vec<Complex<f64>> = (0..order).map(|k| {
+				let square: Complex<f64> = (0..k).map(|i| coefficients[i] * coefficients[k - 1 - i]).sum();
+				2. * z * coefficients[k] + square
+			}).collect();
+			next[0] += max_delta;
+
+			// The terms of dz^2 beyond the order are dropped, and the existing error is carried through 2Zdz + dz^2 = (2Z + 2P + error) * error + ...
+			let truncated: f64 = (order..(2 * order)).map(|k| {
+				((k - order)..order).map(|i| coefficients[i] * coefficients[k - 1 - i]).sum::<Complex<f64>>().norm()
+			}).sum();
+			let sum: f64 = coefficients.iter().map(|a| a.norm()).sum();
+			let next_error_bound = truncated + (2. * z.norm() + 2. * sum + error_bound) * error_bound;
+
+			// Stop once the error could be more than the tolerance relative to the smallest possible |dz|, or once any dc could have escaped. |dz| / |dc / max_delta| is at least |a0| - the sum of the other |a_k| - the error bound
+			let next_sum: f64 = next.iter().map(|a| a.norm()).sum();
+			let min_ratio = next[0].norm() - next[1..].iter().map(|a| a.norm()).sum::<f64>() - next_error_bound;
+			let max_z = orbit[iterations + 1].norm() + next_sum + next_error_bound;
+			let within_tolerance = next_error_bound <= tolerance * min_ratio; // Also false if either is NaN
+			if !within_tolerance || max_z > 2. {
+				break;
+			}
+
+			coefficients = next;
+			error_bound = next_error_bound;
+			iterations += 1;
+		}
+
+		SeriesApproximation {
+			iterations: iterations as u32,
+			max_delta,
+			coefficients,
+			error_bound
+		}
+	}
+
+	/// Returns the approximation of dz after `self.iterations` iterations for the offset `dc`
+	pub fn evaluate(&self, dc: Complex<f64>) -> Complex<f64> {
+		if self.iterations == 0 {
+			return Complex::new(0., 0.);
+		}
+
+		let u = dc / self.max_delta;
+		self.coefficients.iter().rev().fold(Complex::new(0., 0.), |acc, &a| (acc + a) * u)
+	}
+}
+
+/// Returns the precision in bits needed for the centre of a viewport with `radius`, with enough to spare for images up to 65536 pixels across
+pub fn precision_for_radius(radius: f64) -> u64 {
+	(64. - radius.log2()).max(64.) as u64
+}
+
+/// Calculates the escape data of every pixel of the mandelbrot set in `viewport`, with given dimensions, and the max_iterations is the amount of detail (50-100 being low, >=1000 being high, default 100)
+///
+/// Specify using `threadedness` whether to calculate using one thread or multiple. Returns the escape data, and statistics about the references and glitches
+pub fn compute_iterations(threadedness: Threadedness, viewport: &DeepViewport, dimensions: (u32, u32), max_iterations: Option<u32>, options: &PerturbationOptions) -> (IterationBuffer, PerturbationStats) {
+	let (width, height) = dimensions;
+	let max_iterations = max_iterations.unwrap_or(fractal::DEFAULT_MAX_ITERATIONS);
+
+	if width == 0 || height == 0 {
+		return (IterationBuffer { width, height, max_iterations, data: Vec::new() }, PerturbationStats::default());
+	}
+
+	let transform = viewport.delta_transform(dimensions);
+
+	let delta = |i: usize| {
+		let (dx, dy) = transform.transform(((i % width as usize) as f64, (i / width as usize) as f64));
+		Complex::new(dx, dy)
+	};
+
+	let mut stats = PerturbationStats::default();
+
+	// Iterate every pixel with the first reference at the centre, skipping the iterations that the series approximation covers for every pixel
+	let mut reference = ReferenceOrbit::new(viewport.centre.clone(), max_iterations);
+	stats.references += 1;
+
+	let corners = [0, width as usize - 1, (height as usize - 1) * width as usize, width as usize * height as usize - 1];
+	let max_delta = corners.iter().map(|&i| delta(i).norm()).fold(0., f64::max);
+	let series = SeriesApproximation::new(&reference.orbit, options.series_order, max_delta, options.series_tolerance);
+	stats.skipped_iterations = series.iterations;
+
+	let all: Vec<usize> = (0..(width as usize * height as usize)).collect();
+	let mut results = iterate_pixels(&threadedness, &all, |i| iterate_pixel(&reference.orbit, delta(i), (series.iterations, series.evaluate(delta(i))), max_iterations, options));
+
+	// Recalculate glitched pixels with a secondary reference at the worst glitched pixel, until there are none or the max references is reached
+	loop {
+		let glitched: Vec<usize> = all.iter().copied().filter(|&i| results[i].glitch.is_some()).collect();
+
+		if glitched.is_empty() || stats.references >= options.max_references {
+			stats.glitched_pixels = glitched.len() as u64;
+			break;
+		}
+
+		let worst = glitched.iter().copied()
+			.min_by(|&a, &b| results[a].glitch.unwrap().total_cmp(&results[b].glitch.unwrap()))
+			.unwrap();
+		let reference_delta = delta(worst);
+
+		let (re, im) = &viewport.centre;
+		let precision = re.precision();
+		let point = (
+			re + &BigFloat::from_f64(reference_delta.re, precision).unwrap(),
+			im + &BigFloat::from_f64(reference_delta.im, precision).unwrap()
+		);
+		reference = ReferenceOrbit::new(point, max_iterations);
+		stats.references += 1;
+
+		let recalculated = iterate_pixels(&threadedness, &glitched, |i| iterate_pixel(&reference.orbit, delta(i) - reference_delta, (0, Complex::new(0., 0.)), max_iterations, options));
+		for (i, result) in glitched.into_iter().zip(recalculated) {
+			results[i] = result;
+		}
+	}
+
+	stats.rebases = results.iter().map(|result| result.rebases as u64).sum();
+
+	let buffer = IterationBuffer {
+		width,
+		height,
+		max_iterations,
+		data: results.into_iter().map(|result| result.data).collect()
+	};
+
+	(buffer, stats)
+}
+
+/// Generates an RGB image of the mandelbrot set in `viewport` coloured with `colour_map`, with given dimensions, and the max_iterations is the amount of detail (50-100 being low, >=1000 being high, default 100)
+///
+/// Specify using `threadedness` whether to calculate using one thread or multiple. Returns the image, and statistics about the references and glitches
+pub fn generate_fractal_image<C>(threadedness: Threadedness, viewport: &DeepViewport, colour_map: &C, dimensions: (u32, u32), max_iterations: Option<u32>, options: &PerturbationOptions) -> (RgbImage, PerturbationStats) where C: ColourMap + Sync + ?Sized {
+	let (width, height) = dimensions;
+
+	let mut img_buffer: Vec<u8> = vec![0; width as usize * height as usize * 3];
+
+	let stats = render_fractal_to(threadedness, ImageBufferFormat::ChannelArray(ChannelArrayFormat::Rgb8(&mut img_buffer)), viewport, colour_map, dimensions, max_iterations, options);
+
+	(RgbImage::from_raw(width, height, img_buffer).unwrap(), stats)
+}
+
+/// Generates an RGB image of the mandelbrot set in `viewport` coloured with `colour_map`, with given dimensions, and the max_iterations is the amount of detail (50-100 being low, >=1000 being high, default 100)
+///
+/// The RGB image is written to the buffer contained within img_buffer_fmt, in the format specified. Specify using `threadedness` whether to calculate using one thread or multiple. Returns statistics about the references and glitches
+pub fn render_fractal_to<C>(threadedness: Threadedness, img_buffer_fmt: ImageBufferFormat, viewport: &DeepViewport, colour_map: &C, dimensions: (u32, u32), max_iterations: Option<u32>, options: &PerturbationOptions) -> PerturbationStats where C: ColourMap + Sync + ?Sized {
+	let (iterations, stats) = compute_iterations(threadedness, viewport, dimensions, max_iterations, options);
+
+	cpu_renderer::colour_iterations_to(threadedness, img_buffer_fmt, &iterations, colour_map);
+
+	stats
+}
+
+/// Calls `pixel` for each of `indices`, returning the results in the same order
+fn iterate_pixels<P>(threadedness: &Threadedness, indices: &[usize], pixel: P) -> Vec<PixelResult> where P: Fn(usize) -> PixelResult + Sync {
+	match threadedness {
+		Threadedness::Singlethreaded => indices.iter().map(|&i| pixel(i)).collect(),
+		#[cfg(feature = "parallel")]
+		Threadedness::Multithreaded => indices.par_iter().map(|&i| pixel(i)).collect()
+	}
+}
+
+/// Iterates the pixel at the offset `dc` from the reference point of `orbit`, starting from the iteration and dz given by `start` (e.g. from a series approximation)
+fn iterate_pixel(orbit: &[Complex<f64>], dc: Complex<f64>, start: (u32, Complex<f64>), max_iterations: u32, options: &PerturbationOptions) -> PixelResult {
+	let (mut i, mut dz) = start;
+	let mut z = orbit[i as usize] + dz;
+	// The index into the reference orbit, which differs from the iteration once the pixel has been rebased
+	let mut m = i as usize;
+	let mut rebases = 0;
+
+	while i < max_iterations && z.norm_sqr() <= 4. {
+		if m + 1 >= orbit.len() {
+			// The reference escaped before this pixel, so it can't be followed any further
+			if options.rebasing {
+				dz = z;
+				m = 0;
+				rebases += 1;
+			} else {
+				return PixelResult::glitched(z, i, 1., rebases);
+			}
+		}
+
+		dz = (2. * orbit[m] + dz) * dz + dc;
+		m += 1;
+		i += 1;
+		z = orbit[m] + dz;
+
+		if options.rebasing {
+			if z.norm_sqr() < dz.norm_sqr() {
+				dz = z;
+				m = 0;
+				rebases += 1;
+			}
+		} else if z.norm_sqr() < options.glitch_tolerance * orbit[m].norm_sqr() {
+			return PixelResult::glitched(z, i, z.norm_sqr() / orbit[m].norm_sqr(), rebases);
+		}
+	}
+
+	PixelResult {
+		data: EscapeData {
+			iterations: i,
+			escaped: i < max_iterations,
+			final_z: z,
+			derivative: None,
+			short_circuit: None
+		},
+		glitch: None,
+		rebases
+	}
+}
+
+impl PixelResult {
+	fn glitched(z: Complex<f64>, iterations: u32, severity: f64, rebases: u32) -> Self {
+		PixelResult {
+			data: EscapeData {
+				iterations,
+				escaped: false,
+				final_z: z,
+				derivative: None,
+				short_circuit: None
+			},
+			glitch: Some(severity),
+			rebases
+		}
+	}
+}
+
+#[test]
+#[cfg(test)]
+fn test_perturbation_matches_direct() {
+	use super::{Fractal, mandelbrot::Mandelbrot};
+
+	// At a shallow zoom f64 is exact enough, so perturbation should give the same escape counts as iterating directly
+	let viewport = Viewport::new(Complex::new(-0.7435, 0.1314), 0.002);
+	let dims = (48, 36);
+	let transform = viewport.to_transform(dims);
+	let direct = cpu_renderer::compute_iterations(Threadedness::Singlethreaded, &Mandelbrot::default(), dims, &transform, Some(500), false);
+
+	for options in [PerturbationOptions::default(), PerturbationOptions::default().rebasing(false)] {
+		let (perturbed, stats) = compute_iterations(Threadedness::Singlethreaded, &DeepViewport::from_viewport(&viewport), dims, Some(500), &options);
+		assert_eq!(stats.glitched_pixels, 0);
+
+		let matching = perturbed.data.iter().zip(&direct.data).filter(|(a, b)| a.iterations == b.iterations).count();
+		assert!(matching as f64 >= 0.99 * direct.data.len() as f64);
+	}
+
+	// An empty image has no pixels to calculate
+	for dims in [(0, 36), (48, 0), (0, 0)] {
+		let (empty, stats) = compute_iterations(Threadedness::Singlethreaded, &DeepViewport::from_viewport(&viewport), dims, Some(500), &PerturbationOptions::default());
+		assert!(empty.data.is_empty());
+		assert_eq!(stats, PerturbationStats::default());
+	}
+
+	// Check the pixel at the reference point itself
+	let (cx, cy) = transform.transform((24., 18.));
+	let centre = Mandelbrot::default().iterate(Complex::new(cx, cy), 500);
+	assert_eq!(compute_iterations(Threadedness::Singlethreaded, &DeepViewport::from_viewport(&viewport), dims, Some(500), &PerturbationOptions::default()).0.get(24, 18).iterations, centre.iterations);
+}
+
+#[test]
+#[cfg(test)]
+fn test_perturbation_deep_zoom() {
+	// Far beyond f64, every pixel would have the same c, but perturbation should still resolve detail
+	let viewport = DeepViewport::parse("-0.743643887037158704752191506114774", "0.131825904205311970493132056385139", 1e-20).unwrap();
+	let (iterations, stats) = compute_iterations(Threadedness::Singlethreaded, &viewport, (32, 24), Some(20000), &PerturbationOptions::default());
+
+	let mut counts: Vec<u32> = iterations.data.iter().map(|data| data.iterations).collect();
+	counts.sort_unstable();
+	counts.dedup();
+	assert!(counts.len() > 10);
+	assert_eq!(stats.glitched_pixels, 0);
+
+	// Rendering directly in double-double precision is still exact at this depth, so should agree with perturbation
+	let dims = (16, 12);
+	let (perturbed, _) = compute_iterations(Threadedness::Singlethreaded, &viewport, dims, Some(20000), &PerturbationOptions::default());
+	let direct = cpu_renderer::compute_iterations(Threadedness::Singlethreaded, &super::mandelbrot::Mandelbrot::default(), dims, &viewport.double_double_transform(dims), Some(20000), false);
+	let matching = perturbed.data.iter().zip(&direct.data).filter(|(a, b)| a.iterations == b.iterations).count();
+	assert!(matching as f64 >= 0.99 * direct.data.len() as f64);
+}
+
+#[test]
+#[cfg(test)]
+fn test_perturbation_far_beyond_double_double() {
+	// Next to the Misiurewicz point i at 1e-100, where neither f64 nor double-double can be checked against, so the two ways of avoiding glitches are checked against each other
+	let viewport = DeepViewport::parse("3e-101", &format!("1.{}2", "0".repeat(100)), 1e-100).unwrap();
+	let dims = (32, 24);
+
+	let (rebased, rebased_stats) = compute_iterations(Threadedness::Singlethreaded, &viewport, dims, Some(2000), &PerturbationOptions::default());
+	let (referenced, referenced_stats) = compute_iterations(Threadedness::Singlethreaded, &viewport, dims, Some(2000), &PerturbationOptions::default().rebasing(false));
+
+	// The orbits pass close to 0, so both the rebasing and the secondary references are needed
+	assert!(rebased_stats.rebases > 0);
+	assert!(referenced_stats.references > 1);
+	assert_eq!(rebased_stats.glitched_pixels, 0);
+	assert_eq!(referenced_stats.glitched_pixels, 0);
+
+	let mut counts: Vec<u32> = rebased.data.iter().map(|data| data.iterations).collect();
+	counts.sort_unstable();
+	counts.dedup();
+	assert!(counts.len() > 10);
+
+	let matching = rebased.data.iter().zip(&referenced.data).filter(|(a, b)| a.iterations == b.iterations).count();
+	assert!(matching as f64 >= 0.99 * rebased.data.len() as f64);
+}
+
+#[test]
+#[cfg(test)]
+fn test_series_approximation() {
+	let viewport = DeepViewport::parse("-0.743643887037158704752191506114774", "0.131825904205311970493132056385139", 1e-20).unwrap();
+	let dims = (32, 24);
+
+	// Most of the iterations of this location are the same for every pixel, so should be skipped without changing the escape counts
+	let (skipped, stats) = compute_iterations(Threadedness::Singlethreaded, &viewport, dims, Some(20000), &PerturbationOptions::default());
+	let (full, full_stats) = compute_iterations(Threadedness::Singlethreaded, &viewport, dims, Some(20000), &PerturbationOptions::default().series_order(0));
+	assert!(stats.skipped_iterations > 1000);
+	assert_eq!(full_stats.skipped_iterations, 0);
+
+	let matching = skipped.data.iter().zip(&full.data).filter(|(a, b)| a.iterations == b.iterations).count();
+	assert!(matching as f64 >= 0.99 * full.data.len() as f64);
+
+	// No pixel escapes before the skipped iterations
+	assert!(skipped.data.iter().all(|data| data.iterations >= stats.skipped_iterations));
+}
+
+#[test]
+#[cfg(test)]
+fn test_series_approximation_error_bound() {
+	// At shallow zooms with few terms the truncation error is far larger than rounding, so the error of the polynomial from dz iterated directly should be within the bound
+	for (re, im) in [(-0.75, 0.1), (0.28, 0.01), (-1.25, 0.02), (-0.1, 0.65), (-0.16, 1.035)] {
+		let orbit = ReferenceOrbit::new((BigFloat::from_f64(re, 64).unwrap(), BigFloat::from_f64(im, 64).unwrap()), 1000).orbit;
+
+		for (order, max_delta) in [(1, 1e-4), (3, 1e-3), (6, 1e-2)] {
+			let series = SeriesApproximation::new(&orbit, order, max_delta, 0.1);
+			assert!(series.iterations > 0);
+
+			for (r, angle) in [(1., 0.), (1., 0.3), (0.7, 2.), (0.4, 4.1), (1., 5.5)] {
+				let dc = Complex::from_polar(r * max_delta, angle);
+				let dz = orbit[..series.iterations as usize].iter().fold(Complex::new(0., 0.), |dz, &z| (2. * z + dz) * dz + dc);
+
+				let bound = series.error_bound * r.powi(order as i32 + 1);
+				assert!((dz - series.evaluate(dc)).norm() <= bound * (1. + 1e-9) + dz.norm() * 1e-12);
+			}
+		}
+	}
+}
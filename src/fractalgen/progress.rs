@@ -0,0 +1,140 @@
+//! This module contains the `RenderOptions` struct, which carries a `CancellationToken` to stop a render early and a callback to report its `Progress`
+//!
+//! Renders are split into units of work (rows, tiles, pixels or GPU dispatches depending on the renderer), and the token is checked and progress reported between them, so a cancelled render stops within one unit of work and returns `Error::Cancelled`
+
+use std::fmt;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use super::Error;
+
+/// A flag shared between clones, which cancels every render it is passed to when set
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken {
+	cancelled: Arc<AtomicBool>
+}
+
+impl CancellationToken {
+	pub fn new() -> Self {
+		CancellationToken::default()
+	}
+
+	/// Cancels renders using this token or any of its clones. Can be called from any thread
+	pub fn cancel(&self) {
+		self.cancelled.store(true, Ordering::Relaxed);
+	}
+
+	pub fn is_cancelled(&self) -> bool {
+		self.cancelled.load(Ordering::Relaxed)
+	}
+}
+
+/// How much of a render is done
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Progress {
+	/// The number of units of work (rows, tiles, pixels or GPU dispatches) completed
+	pub completed: u64,
+	/// The total number of units of work
+	pub total: u64,
+	/// The time since the render started
+	pub elapsed: Duration
+}
+
+impl Progress {
+	/// Returns the fraction of the render that is done, from 0 to 1
+	pub fn fraction(&self) -> f64 {
+		if self.total == 0 { 1. } else { self.completed as f64 / self.total as f64 }
+	}
+
+	/// Estimates the time until the render is done, assuming the remaining units of work take as long as those completed so far. Returns None if nothing has been completed yet
+	pub fn eta(&self) -> Option<Duration> {
+		if self.completed == 0 {
+			None
+		} else {
+			Some(self.elapsed.mul_f64((self.total - self.completed) as f64 / self.completed as f64))
+		}
+	}
+}
+
+/// Options for controlling a render while it runs
+#[derive(Clone, Copy, Default)]
+pub struct RenderOptions<'a> {
+	/// If set, the render stops early with `Error::Cancelled` once the token is cancelled
+	pub cancellation: Option<&'a CancellationToken>,
+	/// Called after each unit of work is completed. Multithreaded renderers may call it from several threads at once, so updates may arrive out of order
+	pub on_progress: Option<&'a (dyn Fn(&Progress) + Sync)>
+}
+
+impl<'a> RenderOptions<'a> {
+	/// Options that never cancel and don't report progress
+	pub fn new() -> Self {
+		RenderOptions::default()
+	}
+
+	/// Builder-style method
+	pub fn cancellation(mut self, cancellation: &'a CancellationToken) -> Self {
+		self.cancellation = Some(cancellation);
+		self
+	}
+
+	/// Builder-style method
+	pub fn on_progress(mut self, on_progress: &'a (dyn Fn(&Progress) + Sync)) -> Self {
+		self.on_progress = Some(on_progress);
+		self
+	}
+
+	pub fn is_cancelled(&self) -> bool {
+		self.cancellation.is_some_and(|token| token.is_cancelled())
+	}
+}
+
+impl fmt::Debug for RenderOptions<'_> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("RenderOptions")
+			.field("cancellation", &self.cancellation)
+			.field("on_progress", &self.on_progress.is_some())
+			.finish()
+	}
+}
+
+/// Tracks the progress of one render with `RenderOptions`, for the renderers to check for cancellation and report progress with
+pub(crate) struct ProgressTracker<'a> {
+	options: &'a RenderOptions<'a>,
+	total: u64,
+	completed: AtomicU64,
+	start: Instant
+}
+
+impl<'a> ProgressTracker<'a> {
+	/// Starts tracking a render of `total` units of work
+	pub(crate) fn new(options: &'a RenderOptions<'a>, total: u64) -> Self {
+		ProgressTracker {
+			options,
+			total,
+			completed: AtomicU64::new(0),
+			start: Instant::now()
+		}
+	}
+
+	/// Returns `Error::Cancelled` if the render has been cancelled
+	pub(crate) fn check(&self) -> Result<(), Error> {
+		if self.options.is_cancelled() {
+			Err(Error::Cancelled)
+		} else {
+			Ok(())
+		}
+	}
+
+	/// Records that `units` more units of work are completed, and reports the progress
+	pub(crate) fn advance(&self, units: u64) {
+		let completed = self.completed.fetch_add(units, Ordering::Relaxed) + units;
+
+		if let Some(on_progress) = self.options.on_progress {
+			on_progress(&Progress {
+				completed,
+				total: self.total,
+				elapsed: self.start.elapsed()
+			});
+		}
+	}
+}
@@ -0,0 +1,312 @@
+//! This module contains the `Renderer` trait, which is implemented by every rendering backend, and the `RenderJob` struct, which describes a render independently of the backend used
+//!
+//! The enum `Backend` allows selecting the backend at runtime, and the functions `render` and `render_to` select one automatically, falling back to the CPU when no GPU is available
+//!
+//! The backends available depend on the enabled features: `CpuMultithreaded` requires `parallel` and `GpuCompute` requires `gpu`
+
+use super::{FractalType, PlaneTransform, Viewport, ImageBufferFormat, ChannelArrayFormat, Error, RenderOptions};
+use super::colouring::ColourMapType;
+use super::fractal::CheckedFractalType;
+use super::mandelbrot::InteriorChecks;
+use super::progress::ProgressTracker;
+use super::cpu_renderer::{single_threaded, subdivision};
+#[cfg(feature = "parallel")]
+use super::cpu_renderer::multi_threaded;
+#[cfg(feature = "gpu")]
+use super::common;
+#[cfg(feature = "gpu")]
+use super::gpu_renderer::compute::ComputeRenderer;
+use image::{DynamicImage, RgbImage, RgbaImage};
+
+/// The format of the image generated by `Renderer::generate_fractal_image`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OutputFormat {
+	Rgb8,
+	Rgba8
+}
+
+/// Describes a render of a fractal: the fractal, dimensions, transformation from the image coordinate plane to the complex plane, max iterations, colouring and output format
+#[derive(Clone, Copy)]
+pub struct RenderJob {
+	pub fractal: FractalType,
+	pub dimensions: (u32, u32),
+	pub transform: PlaneTransform<f64>,
+	/// The amount of detail (50-100 being low, >=1000 being high, default 100)
+	pub max_iterations: Option<u32>,
+	pub colour_map: ColourMapType,
+	/// Whether to colour by the normalised (fractional) iteration count instead of the number of iterations
+	pub smooth_colouring: bool,
+	/// The checks the CPU renderers use to detect points inside the mandelbrot set without iterating them to the max iterations. Ignored by the GPU renderer
+	pub interior_checks: InteriorChecks,
+	pub output_format: OutputFormat
+}
+
+/// A backend that can render a `RenderJob`
+pub trait Renderer {
+	/// Renders `job`, writing the image to the buffer contained within img_buffer_fmt, in the format specified
+	///
+	/// Rendering on the CPU cannot fail, but other backends fail if e.g. no device is available
+	fn render_fractal_to(&self, job: &RenderJob, img_buffer_fmt: ImageBufferFormat) -> Result<(), Error>;
+
+	/// Same as `render_fractal_to`, but stops early with `Error::Cancelled` if the cancellation token of `options` is cancelled, and reports progress to its progress callback
+	///
+	/// By default the render can only be cancelled before it starts, and progress is reported once when it finishes. The built-in renderers override this to check and report between rows, tiles or GPU dispatches
+	fn render_fractal_to_with_options(&self, job: &RenderJob, img_buffer_fmt: ImageBufferFormat, options: &RenderOptions) -> Result<(), Error> {
+		let tracker = ProgressTracker::new(options, 1);
+		tracker.check()?;
+		self.render_fractal_to(job, img_buffer_fmt)?;
+		tracker.advance(1);
+		Ok(())
+	}
+
+	/// Renders `job`, returning an image in the output format of the job
+	fn generate_fractal_image(&self, job: &RenderJob) -> Result<DynamicImage, Error> {
+		self.generate_fractal_image_with_options(job, &RenderOptions::new())
+	}
+
+	/// Same as `generate_fractal_image`, but can be cancelled and reports progress as `render_fractal_to_with_options` does
+	fn generate_fractal_image_with_options(&self, job: &RenderJob, options: &RenderOptions) -> Result<DynamicImage, Error> {
+		let (width, height) = job.dimensions;
+
+		match job.output_format {
+			OutputFormat::Rgb8 => {
+				let mut img_buffer: Vec<u8> = vec![0; width as usize * height as usize * 3];
+				self.render_fractal_to_with_options(job, ImageBufferFormat::ChannelArray(ChannelArrayFormat::Rgb8(&mut img_buffer)), options)?;
+				Ok(DynamicImage::ImageRgb8(RgbImage::from_raw(width, height, img_buffer).unwrap()))
+			},
+			OutputFormat::Rgba8 => {
+				let mut img_buffer: Vec<u8> = vec![0; width as usize * height as usize * 4];
+				self.render_fractal_to_with_options(job, ImageBufferFormat::ChannelArray(ChannelArrayFormat::Rgba8(&mut img_buffer)), options)?;
+				Ok(DynamicImage::ImageRgba8(RgbaImage::from_raw(width, height, img_buffer).unwrap()))
+			}
+		}
+	}
+}
+
+/// The available rendering backends, for selecting one at runtime. Implements `Renderer` by dispatching to the renderer of each variant
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Backend {
+	CpuSinglethreaded,
+	#[cfg(feature = "parallel")]
+	CpuMultithreaded,
+	/// Subdivides the image on the CPU, which renders the same images as the other CPU backends but faster for the mandelbrot and julia sets (see `subdivision::compute_iterations`). Other fractals and smooth colouring are rendered pixel by pixel
+	CpuSubdivision,
+	#[cfg(feature = "gpu")]
+	GpuCompute
+}
+
+/// The backend used by `render` and `render_to` when no GPU is available
+#[cfg(feature = "parallel")]
+const CPU_BACKEND: Backend = Backend::CpuMultithreaded;
+#[cfg(not(feature = "parallel"))]
+const CPU_BACKEND: Backend = Backend::CpuSinglethreaded;
+
+impl RenderJob {
+	/// Creates a job with the default max iterations, coloured by mapping the number of iterations to hue, and output as RGB
+	pub fn new(fractal: FractalType, dimensions: (u32, u32), transform: PlaneTransform<f64>) -> Self {
+		RenderJob {
+			fractal,
+			dimensions,
+			transform,
+			max_iterations: None,
+			colour_map: ColourMapType::Hue,
+			smooth_colouring: false,
+			interior_checks: InteriorChecks::default(),
+			output_format: OutputFormat::Rgb8
+		}
+	}
+
+	/// Creates a job rendering the region described by `viewport` at the given dimensions, with the same defaults as `RenderJob::new`
+	pub fn from_viewport(fractal: FractalType, dimensions: (u32, u32), viewport: &Viewport) -> Self {
+		RenderJob::new(fractal, dimensions, viewport.to_transform(dimensions))
+	}
+
+	/// Builder-style method
+	pub fn max_iterations(mut self, max_iterations: u32) -> Self {
+		self.max_iterations = Some(max_iterations);
+		self
+	}
+
+	/// Builder-style method
+	pub fn colour_map(mut self, colour_map: ColourMapType) -> Self {
+		self.colour_map = colour_map;
+		self
+	}
+
+	/// Builder-style method
+	pub fn smooth_colouring(mut self, smooth_colouring: bool) -> Self {
+		self.smooth_colouring = smooth_colouring;
+		self
+	}
+
+	/// Builder-style method
+	pub fn interior_checks(mut self, interior_checks: InteriorChecks) -> Self {
+		self.interior_checks = interior_checks;
+		self
+	}
+
+	/// Returns the fractal of the job with its interior checks, as rendered by the CPU renderers
+	pub fn checked_fractal(&self) -> CheckedFractalType {
+		CheckedFractalType {
+			fractal: self.fractal,
+			interior_checks: self.interior_checks
+		}
+	}
+
+	/// Builder-style method
+	pub fn output_format(mut self, output_format: OutputFormat) -> Self {
+		self.output_format = output_format;
+		self
+	}
+}
+
+/// Renders `job` on the GPU if a compute-capable Vulkan device is available, otherwise on the CPU with multiple threads, returning the image in the output format of the job and the backend that was used
+///
+/// Only a missing Vulkan implementation or device causes the CPU to be used - other errors while rendering on the GPU are returned. Without the `gpu` feature the CPU is always used, and without the `parallel` feature it uses one thread
+pub fn render(job: &RenderJob) -> Result<(DynamicImage, Backend), Error> {
+	#[cfg(feature = "gpu")]
+	match Backend::GpuCompute.generate_fractal_image(job) {
+		Ok(img) => return Ok((img, Backend::GpuCompute)),
+		Err(err) if !err.is_device_unavailable() => return Err(err),
+		Err(_) => ()
+	}
+
+	Ok((CPU_BACKEND.generate_fractal_image(job)?, CPU_BACKEND))
+}
+
+/// Renders `job` on the GPU if a compute-capable Vulkan device is available, otherwise on the CPU with multiple threads, writing the image to the buffer contained within img_buffer_fmt, in the format specified, and returning the backend that was used
+///
+/// Only a missing Vulkan implementation or device causes the CPU to be used - other errors while rendering on the GPU are returned. Without the `gpu` feature the CPU is always used, and without the `parallel` feature it uses one thread
+pub fn render_to(job: &RenderJob, img_buffer_fmt: ImageBufferFormat) -> Result<Backend, Error> {
+	#[cfg(feature = "gpu")]
+	{
+		// img_buffer_fmt is consumed by rendering, so render on the GPU to an intermediate buffer that is only written to img_buffer_fmt if it succeeds
+		let (width, height) = job.dimensions;
+		let mut img_buffer: Vec<u8> = vec![0; width as usize * height as usize * 4];
+
+		match Backend::GpuCompute.render_fractal_to(job, ImageBufferFormat::ChannelArray(ChannelArrayFormat::Rgba8(&mut img_buffer))) {
+			Ok(()) => {
+				common::write_rgba8_to(img_buffer_fmt, &img_buffer);
+				return Ok(Backend::GpuCompute);
+			},
+			Err(err) if !err.is_device_unavailable() => return Err(err),
+			Err(_) => ()
+		}
+	}
+
+	CPU_BACKEND.render_fractal_to(job, img_buffer_fmt)?;
+	Ok(CPU_BACKEND)
+}
+
+impl Renderer for Backend {
+	fn render_fractal_to(&self, job: &RenderJob, img_buffer_fmt: ImageBufferFormat) -> Result<(), Error> {
+		self.render_fractal_to_with_options(job, img_buffer_fmt, &RenderOptions::new())
+	}
+
+	fn render_fractal_to_with_options(&self, job: &RenderJob, img_buffer_fmt: ImageBufferFormat, options: &RenderOptions) -> Result<(), Error> {
+		match self {
+			Backend::CpuSinglethreaded => single_threaded::SinglethreadedRenderer.render_fractal_to_with_options(job, img_buffer_fmt, options),
+			#[cfg(feature = "parallel")]
+			Backend::CpuMultithreaded => multi_threaded::MultithreadedRenderer.render_fractal_to_with_options(job, img_buffer_fmt, options),
+			Backend::CpuSubdivision => subdivision::SubdivisionRenderer.render_fractal_to_with_options(job, img_buffer_fmt, options),
+			#[cfg(feature = "gpu")]
+			Backend::GpuCompute => ComputeRenderer.render_fractal_to_with_options(job, img_buffer_fmt, options)
+		}
+	}
+}
+
+#[cfg(test)]
+fn check_renderer(renderer: &dyn Renderer) {
+	use super::colouring::ColourMap;
+	use super::{common, PixelArrayFormat};
+
+	let transform = PlaneTransform::new().scale((0.05, 0.05)).base_offset((40., 30.));
+	let job = RenderJob::new(FractalType::MandelbrotSet, (80, 60), transform).max_iterations(100).colour_map(ColourMapType::Purple);
+
+	// The centre of the image is in the set so should be black, and the top left corner escapes immediately
+	let img = renderer.generate_fractal_image(&job).unwrap().into_rgb8();
+	assert_eq!(img.dimensions(), (80, 60));
+	assert_eq!(img.get_pixel(40, 30).0, [0, 0, 0]);
+	let corner = ColourMapType::Purple.map(1., 100.);
+	assert!((img.get_pixel(0, 0).0[0] as f64 - corner.r).abs() <= 1.);
+
+	// Each output format should contain the same image
+	let rgba = renderer.generate_fractal_image(&job.output_format(OutputFormat::Rgba8)).unwrap().into_rgba8();
+	let mut argb32 = vec![0u32; 80 * 60];
+	renderer.render_fractal_to(&job, ImageBufferFormat::PixelArray(PixelArrayFormat::Argb32(&mut argb32))).unwrap();
+	for (x, y, px) in img.enumerate_pixels() {
+		let [r, g, b] = px.0;
+		assert_eq!(rgba.get_pixel(x, y).0, [r, g, b, 255]);
+		assert_eq!(argb32[(x + y * 80) as usize], common::to_0rgb_u8(r, g, b));
+	}
+}
+
+#[test]
+#[cfg(test)]
+fn test_cpu_singlethreaded_renderer() {
+	check_renderer(&Backend::CpuSinglethreaded);
+}
+
+#[test]
+#[cfg(all(test, feature = "parallel"))]
+fn test_cpu_multithreaded_renderer() {
+	check_renderer(&Backend::CpuMultithreaded);
+}
+
+#[test]
+#[cfg(test)]
+fn test_cpu_subdivision_renderer() {
+	check_renderer(&Backend::CpuSubdivision);
+}
+
+#[test]
+#[cfg(all(test, feature = "gpu"))]
+fn test_gpu_compute_renderer() {
+	check_renderer(&Backend::GpuCompute);
+}
+
+#[test]
+#[cfg(test)]
+fn test_render_fallback() {
+	let transform = PlaneTransform::new().scale((0.05, 0.05)).base_offset((40., 30.));
+	let job = RenderJob::new(FractalType::MandelbrotSet, (80, 60), transform).max_iterations(100);
+
+	// Whichever backend is available should be used, and the CPU fallback should produce the same image as rendering on the CPU directly
+	let (img, backend) = render(&job).unwrap();
+	assert_eq!((img.width(), img.height()), (80, 60));
+	if backend == CPU_BACKEND {
+		assert_eq!(img.into_rgb8(), CPU_BACKEND.generate_fractal_image(&job).unwrap().into_rgb8());
+	}
+
+	let mut rgb = vec![0u8; 80 * 60 * 3];
+	assert_eq!(render_to(&job, ImageBufferFormat::ChannelArray(ChannelArrayFormat::Rgb8(&mut rgb))).unwrap(), backend);
+}
+
+#[test]
+#[cfg(test)]
+fn test_render_cancellation_and_progress() {
+	use super::{CancellationToken, Progress};
+	use std::sync::Mutex;
+
+	let job = RenderJob::new(FractalType::MandelbrotSet, (80, 60), PlaneTransform::new().scale((0.05, 0.05)).base_offset((40., 30.)));
+
+	// Progress is reported for every row until the render is complete
+	let reports: Mutex<Vec<Progress>> = Mutex::new(Vec::new());
+	let on_progress = |progress: &Progress| reports.lock().unwrap().push(*progress);
+	let img = Backend::CpuSinglethreaded.generate_fractal_image_with_options(&job, &RenderOptions::new().on_progress(&on_progress)).unwrap();
+	assert_eq!(img, Backend::CpuSinglethreaded.generate_fractal_image(&job).unwrap());
+	let reports = reports.into_inner().unwrap();
+	assert_eq!(reports.len(), 60);
+	assert_eq!(reports.last().unwrap().fraction(), 1.);
+	assert_eq!(reports.last().unwrap().eta(), Some(std::time::Duration::ZERO));
+
+	// A token cancelled before or during the render stops it
+	let token = CancellationToken::new();
+	let cancel_after_first = |_: &Progress| token.cancel();
+	let options = RenderOptions::new().cancellation(&token).on_progress(&cancel_after_first);
+
+	for backend in [Backend::CpuSinglethreaded, Backend::CpuSubdivision, CPU_BACKEND] {
+		assert!(matches!(backend.generate_fractal_image_with_options(&job, &options), Err(Error::Cancelled)));
+	}
+	assert!(token.is_cancelled());
+}
@@ -0,0 +1,141 @@
+//! This module contains the absolute value and conjugate variants of the mandelbrot set, such as the burning ship and the tricorn, as the `VariantMandelbrot` fractal, and their julia sets as the `VariantJulia` fractal
+//!
+//! Each variant iterates z = f(z) + c, where f is z^2 with the absolute value taken of, or the sign flipped of, some parts of z or z^2. Writing z = x + iy, the variants are listed in `Variant`
+
+use super::common::{PlaneTransform, Scalar};
+use super::fractal::{self, Fractal, EscapeData};
+use color_space::Rgb;
+use num::Complex;
+use super::colouring;
+
+/// The absolute value and conjugate variants of z^2 + c. The discriminants are the values the compute shader uses to select the variant
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Variant {
+	/// The burning ship, iterating z = (|x| + i|y|)^2 + c, so x = x^2 - y^2 + c.re and y = 2|xy| + c.im. The imaginary axis points up, so the ship is upside down unless the transform flips y
+	BurningShip = 0,
+	/// The tricorn or mandelbar, iterating z = conj(z)^2 + c, so x = x^2 - y^2 + c.re and y = -2xy + c.im
+	Tricorn = 1,
+	/// The celtic mandelbrot, iterating z = |re(z^2)| + i im(z^2) + c, so x = |x^2 - y^2| + c.re and y = 2xy + c.im
+	Celtic = 2,
+	/// The buffalo, iterating z = |re(z^2)| - i |im(z^2)| + c, so x = |x^2 - y^2| + c.re and y = -2|xy| + c.im
+	Buffalo = 3,
+	/// The perpendicular burning ship, iterating z = (x - i|y|)^2 + c, so x = x^2 - y^2 + c.re and y = -2x|y| + c.im
+	PerpendicularBurningShip = 4
+}
+
+impl Variant {
+	/// All of the variants, in the order of their discriminants
+	pub const ALL: [Variant; 5] = [Variant::BurningShip, Variant::Tricorn, Variant::Celtic, Variant::Buffalo, Variant::PerpendicularBurningShip];
+
+	/// Returns f(z) + c for this variant
+	pub fn step<T>(&self, z: Complex<T>, c: Complex<T>) -> Complex<T> where T: Scalar {
+		let (x, y) = (z.re, z.im);
+		let re = x * x - y * y;
+		let im = (x + x) * y;
+
+		let (re, im) = match self {
+			Variant::BurningShip => (re, abs(im)),
+			Variant::Tricorn => (re, T::zero() - im),
+			Variant::Celtic => (abs(re), im),
+			Variant::Buffalo => (abs(re), T::zero() - abs(im)),
+			Variant::PerpendicularBurningShip => (re, T::zero() - (x + x) * abs(y))
+		};
+
+		Complex::new(re + c.re, im + c.im)
+	}
+
+	/// Iterates z from `z` with the constant `c` until it escapes or `max_iterations` is reached
+	fn iterate<T>(&self, z: Complex<T>, c: Complex<T>, max_iterations: u32) -> EscapeData where T: Scalar {
+		let mut z = z;
+		let four = T::from_f64(4.);
+
+		let mut i = 0;
+		while i < max_iterations && z.re * z.re + z.im * z.im <= four { // Taking absolute values doesn't change |z^2|, so as for the mandelbrot set if z increases beyond 2 it escapes
+			z = self.step(z, c);
+			i += 1;
+		}
+
+		EscapeData {
+			iterations: i,
+			escaped: i < max_iterations,
+			final_z: Complex::new(z.re.to_f64(), z.im.to_f64()),
+			derivative: None,
+			short_circuit: None
+		}
+	}
+}
+
+/// The variant of the mandelbrot set, iterating z = f(z) + c starting from z = 0, where c is the point being iterated
+///
+/// The variants aren't holomorphic, so the derivative isn't tracked
+#[derive(Clone, Copy, Debug)]
+pub struct VariantMandelbrot {
+	pub variant: Variant
+}
+
+impl VariantMandelbrot {
+	pub fn new(variant: Variant) -> Self {
+		VariantMandelbrot { variant }
+	}
+}
+
+impl<T> Fractal<T> for VariantMandelbrot where T: Scalar {
+	fn iterate(&self, point: Complex<T>, max_iterations: u32) -> EscapeData {
+		self.variant.iterate(Complex::new(T::zero(), T::zero()), point, max_iterations)
+	}
+}
+
+/// The julia set of a variant of the mandelbrot set for the constant c, iterating z = f(z) + c starting from z = the point being iterated
+///
+/// The variants aren't holomorphic, so the derivative isn't tracked
+#[derive(Clone, Copy, Debug)]
+pub struct VariantJulia {
+	pub variant: Variant,
+	pub c: Complex<f64>
+}
+
+impl VariantJulia {
+	pub fn new(variant: Variant, c: Complex<f64>) -> Self {
+		VariantJulia { variant, c }
+	}
+}
+
+impl<T> Fractal<T> for VariantJulia where T: Scalar {
+	fn iterate(&self, point: Complex<T>, max_iterations: u32) -> EscapeData {
+		self.variant.iterate(point, Complex::new(T::from_f64(self.c.re), T::from_f64(self.c.im)), max_iterations)
+	}
+}
+
+pub fn calculate_pixel<T>(variant: Variant, x: u32, y: u32, transform: &PlaneTransform<T>, max_iterations: Option<u32>) -> Rgb where T: Scalar {
+	fractal::calculate_pixel(&VariantMandelbrot::new(variant), &colouring::map_to_colour, x, y, transform, max_iterations)
+}
+
+/// Returns the absolute value of `value`, as `Scalar` has no `abs`
+fn abs<T>(value: T) -> T where T: Scalar {
+	if value < T::zero() { T::zero() - value } else { value }
+}
+
+#[test]
+#[cfg(test)]
+fn test_variants_match_complex_formulas() {
+	let reference = |variant: Variant, z: Complex<f64>, c: Complex<f64>| {
+		let sq = z * z;
+		match variant {
+			Variant::BurningShip => Complex::new(z.re.abs(), z.im.abs()).powi(2) + c,
+			Variant::Tricorn => z.conj().powi(2) + c,
+			Variant::Celtic => Complex::new(sq.re.abs(), sq.im) + c,
+			Variant::Buffalo => Complex::new(sq.re.abs(), -sq.im.abs()) + c,
+			Variant::PerpendicularBurningShip => Complex::new(z.re, -z.im.abs()).powi(2) + c
+		}
+	};
+
+	let c = Complex::new(-1.62, -0.04);
+	for variant in Variant::ALL {
+		for z in [Complex::new(0.3, -0.7), Complex::new(-1.1, 0.4), Complex::new(-0.2, -0.9)] {
+			assert!((variant.step(z, c) - reference(variant, z, c)).norm() < 1e-12);
+		}
+
+		// The julia set started from 0 is the same orbit as the variant of the mandelbrot set at c
+		assert_eq!(Fractal::<f64>::iterate(&VariantMandelbrot::new(variant), c, 500), Fractal::<f64>::iterate(&VariantJulia::new(variant, c), Complex::new(0., 0.), 500));
+	}
+}
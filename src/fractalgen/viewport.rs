@@ -0,0 +1,137 @@
+//! This module contains the `Viewport` struct, which describes the region of the complex plane to render independently of the resolution it is rendered at
+//!
+//! A `PlaneTransform` is expressed in pixel offsets, so is only meaningful for one image size. A `Viewport` can be converted to a `PlaneTransform` for any image size, so the same location can be rendered as a small preview and as a large final image
+
+use super::PlaneTransform;
+use num::Complex;
+
+/// How the radius of a `Viewport` is fitted to images whose aspect ratio is not 1:1
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AspectMode {
+	/// The radius is the distance from the centre to the nearest edge of the image, so the whole circle of the radius is always visible
+	Fit,
+	/// The radius is the distance from the centre to the furthest edge of the image, so the circle of the radius always covers the image
+	Fill,
+	/// The radius is the distance from the centre to the top and bottom edges of the image, and the distance to the left and right edges is the radius multiplied by the given aspect ratio (width / height), regardless of the aspect ratio of the image. Pixels are not square unless the image has the same aspect ratio
+	Stretch(f64)
+}
+
+/// A region of the complex plane, described by its centre, radius and rotation
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Viewport {
+	/// The point in the complex plane at the centre of the image
+	pub centre: Complex<f64>,
+	/// Half the size of the region, see `AspectMode` for which dimension this is
+	pub radius: f64,
+	/// The anticlockwise rotation of the region about its centre, in radians
+	pub rotation: f64,
+	pub aspect_mode: AspectMode
+}
+
+/// The radius that `Viewport::zoom` is relative to, which fits the whole of the mandelbrot set
+pub const DEFAULT_RADIUS: f64 = 2.;
+
+impl Viewport {
+	/// Creates an unrotated viewport where the whole circle of `radius` around `centre` is visible
+	pub fn new(centre: Complex<f64>, radius: f64) -> Self {
+		Viewport {
+			centre,
+			radius,
+			rotation: 0.,
+			aspect_mode: AspectMode::Fit
+		}
+	}
+
+	/// Creates an unrotated viewport magnified by `zoom` relative to `DEFAULT_RADIUS`, e.g. a zoom of 1 shows the whole of the mandelbrot set
+	pub fn from_zoom(centre: Complex<f64>, zoom: f64) -> Self {
+		Viewport::new(centre, DEFAULT_RADIUS / zoom)
+	}
+
+	/// Creates a viewport describing the region shown by `transform` when rendering an image with `dimensions`, using `AspectMode::Fit`
+	///
+	/// Any shear in `transform` or scaling that differs between the axes is lost
+	pub fn from_transform(transform: &PlaneTransform<f64>, dimensions: (u32, u32)) -> Self {
+		let (cx, cy) = transform.transform((dimensions.0 as f64 / 2., dimensions.1 as f64 / 2.));
+		let pixel_size = transform.determinant().abs().sqrt();
+
+		Viewport {
+			centre: Complex::new(cx, cy),
+			radius: pixel_size * dimensions.0.min(dimensions.1) as f64 / 2.,
+			rotation: transform.shear_y.atan2(transform.scale_x),
+			aspect_mode: AspectMode::Fit
+		}
+	}
+
+	/// Returns the magnification of the viewport relative to `DEFAULT_RADIUS`
+	pub fn zoom(&self) -> f64 {
+		DEFAULT_RADIUS / self.radius
+	}
+
+	/// Builder-style method
+	///
+	/// Magnifies the viewport by `factor`, dividing the radius by it
+	pub fn zoom_by(mut self, factor: f64) -> Self {
+		self.radius /= factor;
+		self
+	}
+
+	/// Builder-style method
+	pub fn rotation(mut self, rotation: f64) -> Self {
+		self.rotation = rotation;
+		self
+	}
+
+	/// Builder-style method
+	pub fn aspect_mode(mut self, aspect_mode: AspectMode) -> Self {
+		self.aspect_mode = aspect_mode;
+		self
+	}
+
+	/// Returns the distance in the complex plane between horizontally and vertically adjacent pixels (before rotation) of an image with `dimensions`
+	pub fn pixel_size(&self, dimensions: (u32, u32)) -> (f64, f64) {
+		let (width, height) = (dimensions.0 as f64, dimensions.1 as f64);
+
+		match self.aspect_mode {
+			AspectMode::Fit => {
+				let size = 2. * self.radius / width.min(height);
+				(size, size)
+			},
+			AspectMode::Fill => {
+				let size = 2. * self.radius / width.max(height);
+				(size, size)
+			},
+			AspectMode::Stretch(aspect_ratio) => (2. * self.radius * aspect_ratio / width, 2. * self.radius / height)
+		}
+	}
+
+	/// Returns the transform from the image coordinate plane of an image with `dimensions` to the complex plane, which maps the centre of the image to the centre of the viewport
+	pub fn to_transform(&self, dimensions: (u32, u32)) -> PlaneTransform<f64> {
+		// The centre of the image is the base offset and the centre of the viewport is the transformed offset, so both are exact and rotating about the base offset rotates about the centre
+		PlaneTransform::new()
+			.scale(self.pixel_size(dimensions))
+			.rotate(self.rotation)
+			.base_offset((dimensions.0 as f64 / 2., dimensions.1 as f64 / 2.))
+			.transformed_offset((-self.centre.re, -self.centre.im))
+	}
+}
+
+#[test]
+#[cfg(test)]
+fn test_viewport_resolution_independent() {
+	let viewport = Viewport::new(Complex::new(-0.75, 0.1), 0.5).rotation(0.3);
+
+	// The same fraction of the way across a preview and a larger image should map to the same point
+	let preview = viewport.to_transform((480, 360));
+	let full = viewport.to_transform((15360, 11520));
+	for (x, y) in [(0., 0.), (240., 180.), (100., 300.)] {
+		let (px, py) = preview.transform((x, y));
+		let (fx, fy) = full.transform((x * 32., y * 32.));
+		assert!((px - fx).abs() < 1e-12 && (py - fy).abs() < 1e-12);
+	}
+
+	// The centre of the image maps to the centre of the viewport, and converting back gives the same viewport
+	assert_eq!(preview.transform((240., 180.)), (-0.75, 0.1));
+	let round_trip = Viewport::from_transform(&preview, (480, 360));
+	assert!((round_trip.centre - viewport.centre).norm() < 1e-12);
+	assert!((round_trip.radius - viewport.radius).abs() < 1e-12 && (round_trip.rotation - viewport.rotation).abs() < 1e-12);
+}
@@ -1,19 +1,50 @@
 /// Base module for the library for generating fractals
 pub mod fractalgen {
 	pub mod common;
+	pub mod viewport;
+	pub mod fractal;
+	pub mod iterations;
 	pub mod mandelbrot;
 	pub mod julia;
+	pub mod variants;
+	pub mod multibrot;
+	pub mod newton;
+	pub mod buddhabrot;
 	pub mod cpu_renderer;
+	#[cfg(feature = "gpu")]
 	pub mod gpu_renderer;
 	pub mod colouring;
+	pub mod renderer;
+	pub mod bigfloat;
+	pub mod double_double;
+	pub mod perturbation;
+	pub mod progress;
+	pub mod error;
 
 	// Rexport fractalgen::common::PlaneTransform as fractalgen::PlaneTransform
-	pub use common::{PlaneTransform};
+	pub use common::{PlaneTransform, Scalar};
+	pub use viewport::Viewport;
+	// Rexport the Fractal trait and its escape data as fractalgen::{Fractal, EscapeData}
+	pub use fractal::{Fractal, EscapeData};
+	pub use iterations::IterationBuffer;
+	pub use renderer::{Renderer, RenderJob, Backend, render, render_to};
+	pub use error::Error;
+	pub use progress::{RenderOptions, CancellationToken, Progress};
 
+	/// The built-in fractals, for convenience. Implements `Fractal` by dispatching to the fractal of each variant
 	#[derive(Clone, Copy)]
 	pub enum FractalType {
 		MandelbrotSet,
-		JuliaSet
+		/// The julia set for the constant `c`, e.g. `julia::DEFAULT_C` or a point picked from a mandelbrot render with `mandelbrot::julia_parameter`
+		JuliaSet { c: num::Complex<f64> },
+		/// An absolute value or conjugate variant of the mandelbrot set, such as the burning ship or the tricorn
+		MandelbrotVariant(variants::Variant),
+		/// The julia set of an absolute value or conjugate variant of the mandelbrot set for the constant `c`
+		JuliaVariant { variant: variants::Variant, c: num::Complex<f64> },
+		/// The multibrot set for the real exponent d, iterating z = z^d + c
+		Multibrot { exponent: f64 },
+		/// The julia set of the multibrot set for the real exponent d and the constant `c`
+		Multijulia { exponent: f64, c: num::Complex<f64> }
 	}
 
 	/// Specifies the format of and holds a mutable reference to either a pixel array or channel array
@@ -30,6 +61,7 @@ pub mod fractalgen {
 	/// Specifies the format of and holds a mutable reference to a channel array
 	pub enum ChannelArrayFormat<'a> {
 		Argb8(&'a mut [u8]),
-		Rgb8(&'a mut [u8])
+		Rgb8(&'a mut [u8]),
+		Rgba8(&'a mut [u8])
 	}
 }
\ No newline at end of file